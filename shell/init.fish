@@ -93,7 +93,7 @@ function __leadr_invoke__
     end
 
     function leadr_main
-        set -l cmd (leadr)
+        set -l cmd (LEADR_CURRENT_INPUT=(commandline) leadr)
 
         test -z "$cmd"; and return
 
@@ -1,11 +1,16 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use color_eyre::eyre::Result;
-use crossterm::event::KeyEvent;
+use color_eyre::eyre::{Result, ensure};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-use crate::{keybinding::parse_keysequence, ui::panel::Config as PanelConfig};
+use crate::{
+    keybinding::parse_keysequence,
+    layers::{self, Layer, Merged, Origin},
+    toml_file::{self, UnknownKeys},
+    ui::panel::Config as PanelConfig,
+};
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(schemars::JsonSchema, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
 pub struct Config {
     /// The key binding to activate leadr.
@@ -15,6 +20,28 @@ pub struct Config {
     /// disappearing while leadr is active.
     pub redraw_prompt_line: bool,
 
+    /// The key binding to save the current command line as a new mapping while leadr is active.
+    pub save_key: String,
+
+    /// The mapping file new mappings are saved to, relative to the config directory.
+    pub save_file: PathBuf,
+
+    /// The key binding to show the next page of the panel if not all continuations fit.
+    /// `<PageDown>` always works as well.
+    pub next_page_key: String,
+
+    /// The key binding to show the previous page of the panel. `<PageUp>` always works as well.
+    pub previous_page_key: String,
+
+    /// The key binding to close the panel without inserting anything.
+    pub cancel_key: String,
+
+    /// The key binding to remove the last key of the typed sequence.
+    pub back_key: String,
+
+    /// How to treat unknown keys in config, mapping and theme files.
+    pub unknown_keys: UnknownKeys,
+
     /// Configuration for the keybinding panel.
     pub panel: PanelConfig,
 }
@@ -24,20 +51,62 @@ impl Default for Config {
         Self {
             leadr_key: "<C-g>".into(),
             redraw_prompt_line: true,
+            save_key: "<C-s>".into(),
+            save_file: PathBuf::from("mappings.toml"),
+            next_page_key: "<C-n>".into(),
+            previous_page_key: "<C-p>".into(),
+            cancel_key: "<Esc>".into(),
+            back_key: "<BS>".into(),
+            unknown_keys: UnknownKeys::default(),
             panel: PanelConfig::default(),
         }
     }
 }
 
 impl Config {
-    pub fn load(config_dir: &Path) -> Result<Self> {
-        let config_path = config_dir.join("config.toml");
-        if config_path.exists() {
-            let contents = std::fs::read_to_string(&config_path)?;
-            let config = toml::from_str(&contents)?;
-            Ok(config)
+    /// Loads the configuration from the given directories, lowest priority first.
+    /// Unknown keys are reported as configured, or as [`UnknownKeys::in_session`] describes
+    /// if the configuration is loaded for a session.
+    pub fn load(config_dirs: &[PathBuf], in_session: bool) -> Result<Self> {
+        Ok(Self::load_reporting(config_dirs, in_session)?.0)
+    }
+
+    /// Loads the configuration like [`Config::load`], also returning the merged layers
+    /// to tell where each value comes from.
+    pub fn load_merged(config_dirs: &[PathBuf]) -> Result<(Self, Merged)> {
+        Self::load_reporting(config_dirs, false)
+    }
+
+    fn load_reporting(config_dirs: &[PathBuf], in_session: bool) -> Result<(Self, Merged)> {
+        let mut layers = Vec::new();
+        for config_dir in config_dirs {
+            let mut paths = vec![config_dir.join("config.toml")];
+            if let Some(host_file) = layers::host_specific("config.toml") {
+                paths.push(config_dir.join(host_file));
+            }
+            for path in paths {
+                layers.extend(Layer::read::<Config>(&path)?);
+            }
+        }
+
+        let mut merged = Merged::new(toml::Table::try_from(Config::default())?, Origin::Default);
+        for layer in &layers {
+            merged.merge(layer);
+        }
+
+        // The merged config itself decides how its unknown keys are treated.
+        let (config, unknown): (Config, _) = merged.deserialize(&layers)?;
+        toml_file::report_unknown_keys(&unknown, config.unknown_keys(in_session))?;
+        Ok((config, merged))
+    }
+
+    /// Returns how unknown keys are reported, which is quieter during a session.
+    /// See [`UnknownKeys::in_session`].
+    pub fn unknown_keys(&self, in_session: bool) -> UnknownKeys {
+        if in_session {
+            self.unknown_keys.in_session()
         } else {
-            Ok(Config::default())
+            self.unknown_keys
         }
     }
 
@@ -56,4 +125,44 @@ impl Config {
     pub fn leadr_key_events(&self) -> Result<Vec<KeyEvent>> {
         parse_keysequence(&self.leadr_key)
     }
+
+    /// Returns the save key as a single KeyEvent
+    pub fn save_key_event(&self) -> Result<KeyEvent> {
+        single_key_event("save key", &self.save_key)
+    }
+
+    /// Returns the keys to show the next and previous page of the panel.
+    pub fn page_key_events(&self) -> Result<(KeyEvent, KeyEvent)> {
+        Ok((
+            single_key_event("next page key", &self.next_page_key)?,
+            single_key_event("previous page key", &self.previous_page_key)?,
+        ))
+    }
+
+    /// Returns the keys to close the panel and to go back a key.
+    pub fn cancel_and_back_key_events(&self) -> Result<(KeyEvent, KeyEvent)> {
+        Ok((
+            single_key_event("cancel key", &self.cancel_key)?,
+            single_key_event("back key", &self.back_key)?,
+        ))
+    }
+}
+
+/// Parses a key binding used while leadr is active, which has to be a single chord
+/// that can't be mistaken for part of a key sequence.
+fn single_key_event(name: &str, key: &str) -> Result<KeyEvent> {
+    let events = parse_keysequence(key)?;
+    ensure!(
+        events.len() == 1,
+        "Invalid {name}: '{key}'. Only single-chord keybindings are supported."
+    );
+    let event = events[0];
+    ensure!(
+        !matches!(event.code, KeyCode::Char(_))
+            || event
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT),
+        "Invalid {name}: '{key}'. Characters without <C-…> or <M-…> are part of key sequences."
+    );
+    Ok(event)
 }
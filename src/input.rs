@@ -1,5 +1,9 @@
 use color_eyre::eyre::Result;
-use crossterm::terminal;
+use crossterm::{
+    ExecutableCommand,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    terminal,
+};
 
 /// Guard that enables raw mode on creation and disables it on drop.
 pub struct RawModeGuard;
@@ -19,3 +23,23 @@ impl Drop for RawModeGuard {
         let _ = terminal::disable_raw_mode();
     }
 }
+
+/// Guard that makes the terminal report mouse events on creation and stops it on drop.
+pub struct MouseCaptureGuard;
+
+impl MouseCaptureGuard {
+    /// Enables mouse capture on the terminal leadr draws to.
+    pub fn new() -> Result<Self> {
+        let mut tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
+        tty.execute(EnableMouseCapture)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for MouseCaptureGuard {
+    fn drop(&mut self) {
+        if let Ok(mut tty) = std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+            let _ = tty.execute(DisableMouseCapture);
+        }
+    }
+}
@@ -4,5 +4,5 @@ mod nushell;
 mod parse;
 mod shell_binding;
 
-pub use parse::parse_keysequence;
+pub use parse::{format_key_event, format_keysequence, parse_keysequence};
 pub use shell_binding::{Shell, keyevents_to_shell_binding};
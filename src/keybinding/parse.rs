@@ -75,6 +75,10 @@ fn parse_vim_key(key: &str) -> Result<KeyEvent> {
             "DOWN" => KeyCode::Down,
             "LEFT" => KeyCode::Left,
             "RIGHT" => KeyCode::Right,
+            "PAGEUP" => KeyCode::PageUp,
+            "PAGEDOWN" => KeyCode::PageDown,
+            "BS" | "BACKSPACE" => KeyCode::Backspace,
+            "LT" => KeyCode::Char('<'),
             k if k.starts_with('F') => {
                 let n = k[1..].parse::<u8>().wrap_err_with(|| {
                     format!("Invalid leadr keymap: <{key}>. '{k}' is not a valid function key.")
@@ -108,6 +112,52 @@ fn parse_vim_key(key: &str) -> Result<KeyEvent> {
     })
 }
 
+/// Formats a key in Vim notation, e.g. `x`, `<C-x>` or `<Esc>`. The inverse of [`parse_vim_key`].
+pub fn format_key_event(event: &KeyEvent) -> String {
+    let name = match event.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char('<') => "lt".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "CR".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Backspace => "BS".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        other => format!("{other:?}"),
+    };
+
+    let mut modifiers = String::new();
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        modifiers.push_str("C-");
+    }
+    if event.modifiers.contains(KeyModifiers::ALT) {
+        modifiers.push_str("M-");
+    }
+    if event.modifiers.contains(KeyModifiers::SHIFT) {
+        modifiers.push_str("S-");
+    }
+
+    if modifiers.is_empty() && matches!(event.code, KeyCode::Char(c) if c != ' ' && c != '<') {
+        name
+    } else {
+        format!("<{modifiers}{name}>")
+    }
+}
+
+/// Formats a typed key sequence in Vim notation, e.g. `g<Space>s`.
+pub fn format_keysequence(sequence: &str) -> String {
+    sequence
+        .chars()
+        .map(|c| format_key_event(&KeyEvent::from(KeyCode::Char(c))))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,6 +192,7 @@ mod tests {
         assert_eq!(parse_vim_key("<Esc>").unwrap().code, KeyCode::Esc);
         assert_eq!(parse_vim_key("<Up>").unwrap().code, KeyCode::Up);
         assert_eq!(parse_vim_key("<Down>").unwrap().code, KeyCode::Down);
+        assert_eq!(parse_vim_key("<PageDown>").unwrap().code, KeyCode::PageDown);
         assert_eq!(parse_vim_key("<Left>").unwrap().code, KeyCode::Left);
         assert_eq!(parse_vim_key("<Right>").unwrap().code, KeyCode::Right);
     }
@@ -184,6 +235,17 @@ mod tests {
         assert_eq!(events[2].code, KeyCode::Char('c'));
     }
 
+    #[test]
+    fn test_format_roundtrip() {
+        for key in [
+            "x", "<C-x>", "<M-S-x>", "<CR>", "<Esc>", "<BS>", "<Space>", "<lt>", "<F5>",
+        ] {
+            let event = parse_vim_key(key).unwrap();
+            assert_eq!(format_key_event(&event), key);
+        }
+        assert_eq!(format_keysequence("g s<"), "g<Space>s<lt>");
+    }
+
     #[test]
     fn test_parse_keysequence_vim_style() {
         let events = parse_keysequence("<C-x><M-Enter>").unwrap();
@@ -0,0 +1,292 @@
+//! Layered configuration.
+//!
+//! leadr reads its configuration from several directories: system-wide defaults
+//! (`$XDG_CONFIG_DIRS/leadr` or `/etc/leadr`) first, then the user's config directory.
+//! Within each directory, `config.<hostname>.toml` is applied on top of `config.toml`.
+//! Later layers override earlier ones field by field, and the origin of every value is tracked
+//! so the effective configuration can be explained.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{Result, WrapErr};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::toml_file::{self, UnknownKeys};
+
+/// Returns the system-wide config directories for the given value of `$XDG_CONFIG_DIRS`,
+/// lowest priority first.
+pub fn system_config_dirs(xdg_config_dirs: Option<&str>) -> Vec<PathBuf> {
+    match xdg_config_dirs {
+        // Directories listed first take precedence, so they have to be applied last.
+        Some(dirs) if !dirs.is_empty() => dirs
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .rev()
+            .map(|dir| Path::new(dir).join("leadr"))
+            .collect(),
+        _ => vec![PathBuf::from("/etc/leadr")],
+    }
+}
+
+/// Returns the host specific variant of a file name, e.g. `config.myhost.toml`.
+pub fn host_specific(file_name: &str) -> Option<String> {
+    let hostname = gethostname::gethostname().into_string().ok()?;
+    let hostname = hostname.split('.').next()?.to_string();
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    (!hostname.is_empty()).then(|| format!("{stem}.{hostname}.{extension}"))
+}
+
+/// A TOML file contributing to the effective configuration.
+pub struct Layer {
+    pub path: PathBuf,
+    pub contents: String,
+    pub table: toml::Table,
+    /// Descriptions of the keys in this file that are unknown to the type it was checked against.
+    pub unknown: Vec<String>,
+}
+
+impl Layer {
+    /// Reads a layer from disk. Returns `Ok(None)` if the file doesn't exist.
+    ///
+    /// The file is checked against `T` on its own, so invalid values are reported with their
+    /// location before any merging happens.
+    pub fn read<T: DeserializeOwned>(path: &Path) -> Result<Option<Self>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)?;
+        let (_, unknown) = toml_file::parse_collecting_unknown::<T>(path, &contents)?;
+        let table = toml_file::parse(path, &contents, UnknownKeys::Ignore)?;
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            contents,
+            table,
+            unknown,
+        }))
+    }
+}
+
+/// Where a value of the effective configuration comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// The result of merging several layers, remembering the origin of every value.
+pub struct Merged {
+    pub table: toml::Table,
+    pub files: Vec<PathBuf>,
+    origins: BTreeMap<Vec<String>, Origin>,
+}
+
+impl Merged {
+    pub fn new(base: toml::Table, origin: Origin) -> Self {
+        let mut merged = Self {
+            table: toml::Table::new(),
+            files: Vec::new(),
+            origins: BTreeMap::new(),
+        };
+        merged.merge_table(base, &origin);
+        merged
+    }
+
+    /// Applies a layer on top of everything merged so far.
+    pub fn merge(&mut self, layer: &Layer) {
+        self.merge_table(layer.table.clone(), &Origin::File(layer.path.clone()));
+        self.files.push(layer.path.clone());
+    }
+
+    fn merge_table(&mut self, table: toml::Table, origin: &Origin) {
+        let mut table_origins = Vec::new();
+        merge_into(&mut self.table, table, &mut Vec::new(), &mut table_origins);
+        for path in table_origins {
+            self.origins.insert(path, origin.clone());
+        }
+    }
+
+    /// Returns the origin of the value at the given path, or of its closest parent.
+    pub fn origin(&self, segments: &[String]) -> Option<&Origin> {
+        (0..=segments.len())
+            .rev()
+            .find_map(|length| self.origins.get(&segments[..length]))
+    }
+
+    /// Renders the value deserialized from the merged table as TOML,
+    /// annotating every field with its origin.
+    pub fn annotated<T: Serialize>(&self, value: &T) -> Result<String> {
+        let mut document = toml::to_string(value)?.parse::<toml_edit::DocumentMut>()?;
+        self.annotate(document.as_table_mut(), &mut Vec::new());
+        Ok(document.to_string())
+    }
+
+    fn annotate(&self, table: &mut dyn toml_edit::TableLike, path: &mut Vec<String>) {
+        for (key, item) in table.iter_mut() {
+            path.push(key.get().to_string());
+            if let Some(child) = item.as_table_like_mut() {
+                self.annotate(child, path);
+            } else if let Some(value) = item.as_value_mut()
+                && let Some(origin) = self.origin(path)
+            {
+                value.decor_mut().set_suffix(format!("  # {origin}"));
+            }
+            path.pop();
+        }
+    }
+
+    /// Deserializes the merged table, describing unknown keys with their location
+    /// in the layer they came from.
+    pub fn deserialize<T: DeserializeOwned>(&self, layers: &[Layer]) -> Result<(T, Vec<String>)> {
+        let files = layers
+            .iter()
+            .map(|layer| layer.path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (value, unknown_paths) =
+            toml_file::from_value_collecting_unknown(toml::Value::Table(self.table.clone()))
+                .wrap_err_with(|| format!("Invalid configuration in {files}"))?;
+
+        let unknown = unknown_paths
+            .iter()
+            .map(|segments| {
+                let layer = match self.origin(segments) {
+                    Some(Origin::File(path)) => layers.iter().find(|layer| &layer.path == path),
+                    _ => None,
+                };
+                match layer {
+                    Some(layer) => {
+                        toml_file::describe_unknown_key(&layer.path, &layer.contents, segments)
+                    }
+                    None => format!("unknown key '{}'", segments.join(".")),
+                }
+            })
+            .collect();
+
+        Ok((value, unknown))
+    }
+}
+
+/// Recursively merges `overlay` into `base`, collecting the paths of all values set by it.
+fn merge_into(
+    base: &mut toml::Table,
+    overlay: toml::Table,
+    path: &mut Vec<String>,
+    set_paths: &mut Vec<Vec<String>>,
+) {
+    for (key, value) in overlay {
+        path.push(key.clone());
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_into(base_table, overlay_table, path, set_paths);
+            }
+            (_, value) => {
+                set_paths.push(path.clone());
+                base.insert(key, value);
+            }
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(path: &str, contents: &str) -> Layer {
+        Layer {
+            path: PathBuf::from(path),
+            contents: contents.to_string(),
+            table: contents.parse().unwrap(),
+            unknown: Vec::new(),
+        }
+    }
+
+    fn segments(path: &str) -> Vec<String> {
+        path.split('.').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_merge_field_by_field() {
+        let base: toml::Table = "a = 1\n[panel]\nenabled = true\ndelay_ms = 500\n"
+            .parse()
+            .unwrap();
+        let mut merged = Merged::new(base, Origin::Default);
+        merged.merge(&layer(
+            "/etc/leadr/config.toml",
+            "[panel]\ndelay_ms = 100\n",
+        ));
+        merged.merge(&layer("/home/config.toml", "a = 2\n"));
+
+        assert_eq!(
+            merged.table.to_string(),
+            "a = 2\n\n[panel]\nenabled = true\ndelay_ms = 100\n"
+        );
+        assert_eq!(
+            merged.origin(&segments("a")),
+            Some(&Origin::File("/home/config.toml".into()))
+        );
+        assert_eq!(
+            merged.origin(&segments("panel.delay_ms")),
+            Some(&Origin::File("/etc/leadr/config.toml".into()))
+        );
+        assert_eq!(
+            merged.origin(&segments("panel.enabled")),
+            Some(&Origin::Default)
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_point_to_their_layer() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            #[allow(dead_code)]
+            a: i64,
+        }
+
+        let layers = vec![
+            layer("/etc/leadr/config.toml", "a = 1\n"),
+            layer("/home/config.toml", "a = 2\ntypo = 3\n"),
+        ];
+        let mut merged = Merged::new(toml::Table::new(), Origin::Default);
+        for layer in &layers {
+            merged.merge(layer);
+        }
+
+        let (_, unknown): (Config, _) = merged.deserialize(&layers).unwrap();
+        assert_eq!(unknown.len(), 1);
+        assert!(
+            unknown[0].starts_with("/home/config.toml:2:1: unknown key 'typo'"),
+            "{}",
+            unknown[0]
+        );
+    }
+
+    #[test]
+    fn test_system_config_dirs_precedence() {
+        assert_eq!(
+            system_config_dirs(Some("/first:/second")),
+            vec![
+                PathBuf::from("/second/leadr"),
+                PathBuf::from("/first/leadr")
+            ]
+        );
+        assert_eq!(system_config_dirs(None), vec![PathBuf::from("/etc/leadr")]);
+        assert_eq!(
+            system_config_dirs(Some("")),
+            vec![PathBuf::from("/etc/leadr")]
+        );
+    }
+}
@@ -7,14 +7,22 @@ pub mod config;
 mod cursor;
 mod input;
 mod keybinding;
+pub mod layers;
+pub mod mapping_file;
 pub mod mappings;
+pub mod schema;
 pub mod session;
 pub mod shell;
+pub mod suggest;
+#[cfg(test)]
+mod test_util;
+mod toml_file;
 pub mod ui;
 
 pub use config::Config;
-pub use input::RawModeGuard;
+pub use input::{MouseCaptureGuard, RawModeGuard};
 pub use mappings::{InsertType, Mapping, Mappings};
 pub use session::{LeadrSession, SessionResult};
 pub use shell::{init_bash, init_fish, init_nushell, init_zsh};
+pub use toml_file::UnknownKeys;
 pub use ui::{panel::Panel, symbols::Symbols, theme::Theme};
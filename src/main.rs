@@ -3,17 +3,33 @@
 //! Define key sequences that expand into commands.
 //! Inspired by the (Neo)Vim leader key.
 
-use std::path::PathBuf;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use color_eyre::eyre::{Result, WrapErr, eyre};
 use directories::ProjectDirs;
 
-use leadr::{Config, LeadrSession, Mappings, SessionResult, Theme};
+use leadr::{
+    Config, InsertType, LeadrSession, Mapping, Mappings, SessionResult, Theme, layers,
+    mapping_file,
+    schema::{self, SchemaKind},
+    suggest,
+    ui::{color::ColorSupport, preview},
+};
 
 #[derive(Parser)]
 #[command(about, version)]
 struct Cli {
+    #[arg(
+        long,
+        requires = "list",
+        help = "Also list mappings whose required programs are missing"
+    )]
+    all: bool,
+
     #[arg(long, help = "Generate initialization script for Bash")]
     bash: bool,
 
@@ -29,8 +45,99 @@ struct Cli {
     #[arg(long, help = "Generate initialization script for NuShell")]
     nu: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KIND",
+        help = "Print the JSON Schema of a configuration file"
+    )]
+    schema: Option<SchemaKind>,
+
+    #[arg(
+        long,
+        help = "Print the effective configuration and where each value comes from"
+    )]
+    show_config: bool,
+
+    #[arg(long, help = "List all themes and preview them")]
+    themes: bool,
+
+    #[arg(long, help = "Suggest mappings based on your shell history")]
+    suggest: bool,
+
+    #[arg(
+        long,
+        requires = "suggest",
+        value_name = "FILE",
+        help = "Ask which suggestions to keep and save them to FILE, relative to the config directory"
+    )]
+    save_suggestions: Option<PathBuf>,
+
     #[arg(long, help = "Generate initialization script for Zsh")]
     zsh: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    /// Whether no other command is given, so a session showing the panel is started.
+    fn starts_session(&self) -> bool {
+        self.command.is_none()
+            && !(self.bash
+                || self.fish
+                || self.nu
+                || self.zsh
+                || self.list
+                || self.themes
+                || self.suggest
+                || self.show_config)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Add a new mapping")]
+    Add {
+        #[arg(help = "Key sequence of the new mapping")]
+        sequence: String,
+
+        #[arg(help = "Command to insert or execute")]
+        command: String,
+
+        #[arg(long, short = 'd', help = "Description of the mapping")]
+        description: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = InsertType::Replace, help = "How to insert the command")]
+        insert_type: InsertType,
+
+        #[arg(long, help = "Evaluate the command before inserting it")]
+        evaluate: bool,
+
+        #[arg(long, help = "Execute the command immediately")]
+        execute: bool,
+
+        #[arg(
+            long,
+            help = "Mapping file to write to, relative to the config directory [default: mappings.toml]"
+        )]
+        file: Option<PathBuf>,
+    },
+
+    #[command(about = "Remove a mapping")]
+    Remove {
+        #[arg(help = "Key sequence of the mapping to remove")]
+        sequence: String,
+    },
+
+    #[command(about = "Move a mapping to a different key sequence")]
+    Mv {
+        #[arg(help = "Current key sequence")]
+        old: String,
+
+        #[arg(help = "New key sequence")]
+        new: String,
+    },
 }
 
 fn main() -> Result<()> {
@@ -38,6 +145,11 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    if let Some(kind) = cli.schema {
+        print!("{}", schema::generate(kind)?);
+        return Ok(());
+    }
+
     let config_dir =
         get_config_dir().wrap_err("Failed to determine the configuration directory.")?;
 
@@ -49,10 +161,49 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let config = Config::load(&config_dir).wrap_err("Failed to load config.")?;
-    let mappings = Mappings::load(&config_dir).wrap_err("Failed to load mappings.")?;
-    let theme =
-        Theme::load(&config_dir, &config.panel.theme_name).wrap_err("Failed to load theme.")?;
+    // System-wide directories provide defaults, the user's directory overrides them.
+    // New mappings are written to the user's directory, and mappings are only removed or
+    // moved if no system-wide file defines them.
+    let xdg_config_dirs = std::env::var("XDG_CONFIG_DIRS").ok();
+    let mut config_dirs = layers::system_config_dirs(xdg_config_dirs.as_deref());
+    config_dirs.push(config_dir.clone());
+
+    if cli.show_config {
+        let (config, merged) =
+            Config::load_merged(&config_dirs).wrap_err("Failed to load config.")?;
+        if merged.files.is_empty() {
+            println!("# No config files found, using defaults.");
+        } else {
+            println!("# Merged from, lowest priority first:");
+            for file in &merged.files {
+                println!("#   {}", file.display());
+            }
+        }
+        println!();
+        print!("{}", merged.annotated(&config)?);
+        return Ok(());
+    }
+
+    let in_session = cli.starts_session();
+    let config = Config::load(&config_dirs, in_session).wrap_err("Failed to load config.")?;
+    let unknown_keys = config.unknown_keys(in_session);
+    let mut mappings =
+        Mappings::load(&config_dirs, unknown_keys).wrap_err("Failed to load mappings.")?;
+
+    if let Some(command) = cli.command {
+        return run_command(command, &config_dir, &mappings);
+    }
+
+    let color_support = ColorSupport::detect();
+    mappings.approximate_colors(color_support);
+    let theme_name = (config.panel.theme.extends.as_deref()).unwrap_or(&config.panel.theme_name);
+    let mut theme = Theme::load(&config_dirs, theme_name, unknown_keys)
+        .wrap_err("Failed to load theme.")?
+        .with_overrides(&config.panel.theme)
+        .approximate(color_support);
+    if !config.panel.color.use_color() {
+        theme = theme.monochrome();
+    }
 
     if cli.bash {
         let script =
@@ -80,22 +231,153 @@ fn main() -> Result<()> {
     }
 
     if cli.list {
-        println!("{}", mappings.render_table());
+        println!("{}", mappings.render_table(cli.all));
         return Ok(());
     }
 
-    let mut session = LeadrSession::new(mappings, config, theme);
+    if cli.themes {
+        return run_themes(&config_dirs, &config, color_support);
+    }
+
+    if cli.suggest {
+        return run_suggest(&config_dir, &mappings, cli.save_suggestions.as_deref());
+    }
+
+    let mut session = LeadrSession::new(mappings, config, theme, config_dir);
 
     match session.run().wrap_err("Failed to execute leadr session.")? {
         SessionResult::Command(command) => {
             print!("{}", command);
         }
-        SessionResult::NoMatch | SessionResult::Cancelled => {}
+        SessionResult::NoMatch | SessionResult::Cancelled | SessionResult::Saved => {}
     }
 
     Ok(())
 }
 
+fn run_command(command: Command, config_dir: &Path, mappings: &Mappings) -> Result<()> {
+    match command {
+        Command::Add {
+            sequence,
+            command,
+            description,
+            insert_type,
+            evaluate,
+            execute,
+            file,
+        } => {
+            let path = mapping_file::resolve_path(config_dir, file.as_deref())?;
+            let mapping = Mapping {
+                command,
+                description,
+                insert_type,
+                evaluate,
+                execute,
+                ..Default::default()
+            };
+            mapping_file::add_mapping(mappings, &sequence, mapping, &path)
+                .wrap_err("Failed to add mapping.")?;
+            println!("Added mapping '{}' to {:?}", sequence, path);
+        }
+        Command::Remove { sequence } => {
+            let paths = mapping_file::remove_mapping(mappings, &sequence, config_dir)
+                .wrap_err("Failed to remove mapping.")?;
+            for path in paths {
+                println!("Removed mapping '{}' from {:?}", sequence, path);
+            }
+        }
+        Command::Mv { old, new } => {
+            let paths = mapping_file::rename_mapping(mappings, &old, &new, config_dir)
+                .wrap_err("Failed to move mapping.")?;
+            for path in paths {
+                println!("Moved mapping '{}' to '{}' in {:?}", old, new, path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_suggest(config_dir: &Path, mappings: &Mappings, save_to: Option<&Path>) -> Result<()> {
+    let mut history = Vec::new();
+    for file in suggest::history_files() {
+        history.extend(
+            suggest::read_history(&file)
+                .wrap_err_with(|| format!("Failed to read history file {:?}", file))?,
+        );
+    }
+
+    let suggestions = suggest::suggest(&history, mappings);
+    if suggestions.is_empty() {
+        println!("No suggestions found.");
+        return Ok(());
+    }
+    print!("{}", suggest::render_table(&suggestions));
+
+    let Some(save_to) = save_to else {
+        return Ok(());
+    };
+    let path = mapping_file::resolve_path(config_dir, Some(save_to))?;
+
+    println!();
+    let mut accepted = Vec::new();
+    for suggestion in &suggestions {
+        if confirm(&format!(
+            "Add '{}' → {}?",
+            suggestion.sequence, suggestion.command
+        ))? {
+            accepted.push((suggestion.sequence.clone(), suggestion.to_mapping()));
+        }
+    }
+
+    if accepted.is_empty() {
+        return Ok(());
+    }
+    let count = accepted.len();
+    mapping_file::add_mappings(mappings, accepted, &path).wrap_err("Failed to save mappings.")?;
+    println!("Added {} mappings to {:?}", count, path);
+
+    Ok(())
+}
+
+fn run_themes(config_dirs: &[PathBuf], config: &Config, color_support: ColorSupport) -> Result<()> {
+    let builtin = Theme::BUILTIN_NAMES
+        .iter()
+        .map(|name| (name.to_string(), "builtin"));
+    let user = Theme::user_theme_names(config_dirs)?
+        .into_iter()
+        .map(|name| (name, "themes/"));
+
+    for (name, source) in builtin.chain(user) {
+        let active = if name == config.panel.theme_name {
+            ", active"
+        } else {
+            ""
+        };
+        println!("{name} ({source}{active})");
+        match Theme::load(config_dirs, &name, config.unknown_keys) {
+            Ok(theme) => {
+                let mut theme = theme.approximate(color_support);
+                if !config.panel.color.use_color() {
+                    theme = theme.monochrome();
+                }
+                println!("{}", preview::render_sample(config, &theme)?);
+            }
+            Err(e) => println!("  Failed to load theme: {e:#}\n"),
+        }
+    }
+
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
 fn get_config_dir() -> Result<PathBuf> {
     if let Ok(custom_path) = std::env::var("LEADR_CONFIG_DIR") {
         Ok(PathBuf::from(custom_path))
@@ -0,0 +1,562 @@
+//! Format-preserving edits of mapping files.
+//!
+//! Mapping files are hand-written and usually contain comments and a deliberate ordering.
+//! Re-serializing them via `toml::to_string` would throw both away, so edits are applied to a
+//! `toml_edit` document instead, touching only the entries that actually change.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use toml_edit::{DocumentMut, Item, Key, Table};
+
+use crate::{Mapping, Mappings, mappings::RESERVED_KEYS};
+
+/// A single mapping file loaded for editing.
+pub struct MappingFile {
+    path: PathBuf,
+    document: DocumentMut,
+    /// The prefix this file's mappings are mounted under.
+    prefix: String,
+}
+
+impl MappingFile {
+    /// Opens the mapping file at `path`. A missing file is treated as empty.
+    ///
+    /// Sequences are mounted under the prefix the file had when `mappings` were loaded, which
+    /// includes the prefixes of the files including it. Files that weren't loaded only use
+    /// their own `[meta] prefix`.
+    pub fn open(path: &Path, mappings: &Mappings) -> Result<Self> {
+        let contents = if path.exists() {
+            fs::read_to_string(path)?
+        } else {
+            String::new()
+        };
+        let document = contents
+            .parse::<DocumentMut>()
+            .wrap_err_with(|| format!("Failed to parse {:?}", path))?;
+
+        let prefix = match mappings.source_file(path) {
+            Some(source) => source.prefix.clone(),
+            None => document
+                .get("meta")
+                .and_then(|meta| meta.get("prefix"))
+                .and_then(|prefix| prefix.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            document,
+            prefix,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The prefix this file's mappings are mounted under.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the key of `sequence` within this file, i.e. without the file's prefix.
+    fn key(&self, sequence: &str) -> Result<String> {
+        let prefix = self.prefix();
+        let key = sequence
+            .strip_prefix(prefix)
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| {
+                eyre!(
+                    "Mapping '{sequence}' doesn't fit the prefix '{prefix}' of {:?}",
+                    self.path
+                )
+            })?;
+        if RESERVED_KEYS.contains(&key) {
+            return Err(eyre!(
+                "Mapping '{sequence}' can't be stored in {:?}: '{key}' is a reserved key of mapping files",
+                self.path
+            ));
+        }
+        Ok(key.to_string())
+    }
+
+    pub fn contains(&self, sequence: &str) -> bool {
+        self.key(sequence)
+            .is_ok_and(|key| self.document.contains_key(&key))
+    }
+
+    /// Appends a new mapping to the end of the file.
+    pub fn insert(&mut self, sequence: &str, mapping: &Mapping) -> Result<()> {
+        let key = self.key(sequence)?;
+        if self.contains(sequence) {
+            return Err(eyre!(
+                "Mapping '{sequence}' already exists in {:?}",
+                self.path
+            ));
+        }
+        let mut table = mapping_to_table(mapping)?;
+        if !self.document.is_empty() {
+            table.decor_mut().set_prefix("\n");
+        }
+        self.document.insert(&key, Item::Table(table));
+        Ok(())
+    }
+
+    /// Removes a mapping including any comments attached to it.
+    pub fn remove(&mut self, sequence: &str) -> Result<()> {
+        let key = self.key(sequence)?;
+        self.document
+            .remove(&key)
+            .map(|_| ())
+            .ok_or_else(|| eyre!("Mapping '{sequence}' not found in {:?}", self.path))
+    }
+
+    /// Renames a mapping in place, keeping its position, formatting and comments.
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<()> {
+        if !self.contains(old) {
+            return Err(eyre!("Mapping '{old}' not found in {:?}", self.path));
+        }
+        if self.contains(new) {
+            return Err(eyre!("Mapping '{new}' already exists in {:?}", self.path));
+        }
+        let (old, new) = (self.key(old)?, self.key(new)?);
+
+        // Tables keep their document position, but plain key-value entries are ordered by
+        // insertion, so re-insert everything to keep the renamed entry where it was.
+        let table = self.document.as_table_mut();
+        let keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(entry) = table.remove_entry(&key) {
+                entries.push(entry);
+            }
+        }
+        for (key, item) in entries {
+            let key = if key.get() == old {
+                Key::new(new.as_str()).with_leaf_decor(key.leaf_decor().clone())
+            } else {
+                key
+            };
+            table.insert_formatted(&key, item);
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, self.document.to_string())
+            .wrap_err_with(|| format!("Failed to write {:?}", self.path))
+    }
+}
+
+impl std::fmt::Display for MappingFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+/// Resolves the mapping file new mappings should be written to.
+///
+/// Relative paths are interpreted relative to the config directory. Only files that leadr
+/// actually loads mappings from are accepted, i.e. `mappings.toml` and files in `mappings/`.
+pub fn resolve_path(config_dir: &Path, file: Option<&Path>) -> Result<PathBuf> {
+    let main_file = config_dir.join("mappings.toml");
+    let path = match file {
+        Some(file) => config_dir.join(file),
+        None => return Ok(main_file),
+    };
+
+    let is_toml = path.extension().and_then(|s| s.to_str()) == Some("toml");
+    // `starts_with` compares components, so `mappings/../foo.toml` would pass without this.
+    let leaves_dir = file.is_some_and(|file| {
+        file.components()
+            .any(|component| component == std::path::Component::ParentDir)
+    });
+    let is_loaded =
+        !leaves_dir && (path == main_file || path.starts_with(config_dir.join("mappings")));
+    if !is_toml || !is_loaded {
+        return Err(eyre!(
+            "Mappings are only loaded from {:?} and .toml files in {:?}, not from {:?}",
+            main_file,
+            config_dir.join("mappings"),
+            path
+        ));
+    }
+    Ok(path)
+}
+
+/// Adds a new mapping to `path` after validating it against the already loaded mappings.
+pub fn add_mapping(
+    mappings: &Mappings,
+    sequence: &str,
+    mapping: Mapping,
+    path: &Path,
+) -> Result<()> {
+    add_mappings(mappings, vec![(sequence.to_string(), mapping)], path)
+}
+
+/// Adds several new mappings to `path` at once.
+/// Nothing is written unless all of them are valid.
+pub fn add_mappings(
+    mappings: &Mappings,
+    new_mappings: Vec<(String, Mapping)>,
+    path: &Path,
+) -> Result<()> {
+    let mut file = MappingFile::open(path, mappings)?;
+    let mut updated = mappings.clone();
+
+    for (sequence, mapping) in new_mappings {
+        if let Some(existing) = updated.match_sequence_including_unavailable(&sequence) {
+            return Err(eyre!(
+                "Mapping '{sequence}' already exists (from {})",
+                source_display(existing)
+            ));
+        }
+
+        file.insert(&sequence, &mapping)?;
+        updated.insert(
+            sequence,
+            Mapping {
+                source_file: Some(path.to_path_buf()),
+                defined_in: vec![path.to_path_buf()],
+                ..mapping
+            },
+        );
+    }
+    updated.validate()?;
+
+    file.save()
+}
+
+/// Removes a mapping from every file defining it.
+/// Returns the paths of the modified files.
+pub fn remove_mapping(
+    mappings: &Mappings,
+    sequence: &str,
+    config_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let paths = source_files_of(mappings, sequence, config_dir)?;
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let mut file = MappingFile::open(path, mappings)?;
+        file.remove(sequence)?;
+        files.push(file);
+    }
+    for file in files {
+        file.save()?;
+    }
+
+    Ok(paths)
+}
+
+/// Moves a mapping to a new key sequence within every file defining it.
+/// Returns the paths of the modified files.
+pub fn rename_mapping(
+    mappings: &Mappings,
+    old: &str,
+    new: &str,
+    config_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let paths = source_files_of(mappings, old, config_dir)?;
+    if let Some(existing) = mappings.match_sequence_including_unavailable(new) {
+        return Err(eyre!(
+            "Mapping '{new}' already exists (from {})",
+            source_display(existing)
+        ));
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let mut file = MappingFile::open(path, mappings)?;
+        file.rename(old, new)?;
+        files.push(file);
+    }
+
+    let mut updated = mappings.clone();
+    if let Some(mapping) = updated.remove(old) {
+        updated.insert(new.to_string(), mapping);
+    }
+    updated.validate()?;
+
+    for file in files {
+        file.save()?;
+    }
+
+    Ok(paths)
+}
+
+/// Returns the files defining `sequence`, all of which have to belong to `config_dir`.
+///
+/// Mappings are merged across config directories, so editing only the user's files would
+/// leave behind whatever system-wide files define, and those aren't leadr's to edit.
+fn source_files_of(mappings: &Mappings, sequence: &str, config_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mapping = mappings
+        .match_sequence_including_unavailable(sequence)
+        .ok_or_else(|| eyre!("No mapping found for '{sequence}'"))?;
+    if mapping.defined_in.is_empty() {
+        return Err(eyre!("Mapping '{sequence}' was not loaded from a file"));
+    }
+
+    let foreign: Vec<&PathBuf> = mapping
+        .defined_in
+        .iter()
+        .filter(|path| {
+            mappings
+                .source_file(path)
+                .is_none_or(|source| source.config_dir != config_dir)
+        })
+        .collect();
+    if !foreign.is_empty() {
+        return Err(eyre!(
+            "Mapping '{sequence}' is defined outside of {:?}, in {:?}. \
+             Only mappings from your own config directory can be edited.",
+            config_dir,
+            foreign
+        ));
+    }
+    Ok(mapping.defined_in.clone())
+}
+
+fn source_display(mapping: &Mapping) -> String {
+    mapping
+        .source_file
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "unknown source".to_string())
+}
+
+/// Serializes a mapping into a standalone `[sequence]` table.
+fn mapping_to_table(mapping: &Mapping) -> Result<Table> {
+    let serialized = toml::to_string(mapping)?;
+    let document = serialized.parse::<DocumentMut>()?;
+    let mut table = document.as_table().clone();
+    table.set_implicit(false);
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InsertType, test_util::TempDir, toml_file::UnknownKeys};
+
+    const CONTENTS: &str = r#"# Git mappings
+[gs]
+command = "git status" # most used
+execute = true
+
+# Commit
+[gc]
+command = "git commit"
+"#;
+
+    fn test_file() -> MappingFile {
+        MappingFile {
+            path: PathBuf::from("mappings.toml"),
+            document: CONTENTS.parse().unwrap(),
+            prefix: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let config_dir = Path::new("/config");
+        assert_eq!(
+            resolve_path(config_dir, None).unwrap(),
+            PathBuf::from("/config/mappings.toml")
+        );
+        assert_eq!(
+            resolve_path(config_dir, Some(Path::new("mappings/git.toml"))).unwrap(),
+            PathBuf::from("/config/mappings/git.toml")
+        );
+        assert!(resolve_path(config_dir, Some(Path::new("config.toml"))).is_err());
+        assert!(resolve_path(config_dir, Some(Path::new("mappings/git.txt"))).is_err());
+        assert!(resolve_path(config_dir, Some(Path::new("mappings/../foo.toml"))).is_err());
+        assert!(resolve_path(config_dir, Some(Path::new("mappings/a/../../foo.toml"))).is_err());
+    }
+
+    #[test]
+    fn test_insert_keeps_comments() {
+        let mut file = test_file();
+        file.insert(
+            "ps",
+            &Mapping {
+                command: "sudo ".into(),
+                insert_type: InsertType::Prepend,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let output = file.to_string();
+        assert!(output.starts_with(CONTENTS));
+        assert!(output.ends_with("\n[ps]\ncommand = \"sudo \"\ninsert_type = \"Prepend\"\n"));
+    }
+
+    #[test]
+    fn test_insert_into_empty_file() {
+        let mut file = MappingFile {
+            path: PathBuf::from("mappings.toml"),
+            document: DocumentMut::new(),
+            prefix: String::new(),
+        };
+        file.insert(
+            "gs",
+            &Mapping {
+                command: "git status".into(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(file.to_string(), "[gs]\ncommand = \"git status\"\n");
+    }
+
+    #[test]
+    fn test_insert_existing_fails() {
+        let mut file = test_file();
+        assert!(file.insert("gs", &Mapping::default()).is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut file = test_file();
+        file.remove("gs").unwrap();
+        assert_eq!(
+            file.to_string(),
+            "\n# Commit\n[gc]\ncommand = \"git commit\"\n"
+        );
+        assert!(file.remove("gs").is_err());
+    }
+
+    #[test]
+    fn test_rename_keeps_position() {
+        let mut file = test_file();
+        file.rename("gs", "gS").unwrap();
+        assert_eq!(file.to_string(), CONTENTS.replace("[gs]", "[gS]"));
+    }
+
+    #[test]
+    fn test_rename_inline_keeps_position() {
+        let mut file = MappingFile {
+            path: PathBuf::from("mappings.toml"),
+            document:
+                "a = { command = \"a\" }\n# b\nb = { command = \"b\" }\nc = { command = \"c\" }\n"
+                    .parse()
+                    .unwrap(),
+            prefix: String::new(),
+        };
+        file.rename("b", "x").unwrap();
+        assert_eq!(
+            file.to_string(),
+            "a = { command = \"a\" }\n# b\nx = { command = \"b\" }\nc = { command = \"c\" }\n"
+        );
+    }
+
+    #[test]
+    fn test_prefixed_file() {
+        let mut file = MappingFile {
+            path: PathBuf::from("git.toml"),
+            document: "[meta]\nprefix = \"g\"\n\n[s]\ncommand = \"git status\"\n"
+                .parse()
+                .unwrap(),
+            prefix: "g".into(),
+        };
+        assert!(file.contains("gs"));
+        assert!(!file.contains("s"));
+
+        file.rename("gs", "gt").unwrap();
+        assert!(file.contains("gt"));
+        assert!(file.rename("gt", "xt").is_err());
+
+        file.remove("gt").unwrap();
+        assert!(!file.to_string().contains("[t]"));
+
+        // Keys that aren't mappings can't be used, prefixed or not.
+        let mapping = Mapping {
+            command: "echo hi".into(),
+            ..Default::default()
+        };
+        let err = file.insert("gmeta", &mapping).unwrap_err();
+        assert!(err.to_string().contains("reserved"), "{err}");
+        assert!(test_file().insert("include", &mapping).is_err());
+        assert!(test_file().rename("gs", "meta").is_err());
+    }
+
+    #[test]
+    fn test_included_file_uses_inherited_prefix() {
+        let dir = TempDir::with_files(
+            "mount",
+            &[
+                (
+                    "mappings.toml",
+                    "include = [\"packs/git.toml\"]\n\n[meta]\nprefix = \"g\"\n",
+                ),
+                (
+                    "packs/git.toml",
+                    "[meta]\nprefix = \"i\"\n\n[s]\ncommand = \"git status\"\n",
+                ),
+            ],
+        );
+        let dir = dir.path();
+        let mappings = Mappings::load(&[dir.to_path_buf()], UnknownKeys::Error).unwrap();
+
+        let file = MappingFile::open(&dir.join("packs/git.toml"), &mappings).unwrap();
+        assert_eq!(file.prefix(), "gi");
+        rename_mapping(&mappings, "gis", "git", dir).unwrap();
+        let contents = fs::read_to_string(dir.join("packs/git.toml")).unwrap();
+        assert!(contents.contains("[t]"), "{contents}");
+    }
+
+    #[test]
+    fn test_only_user_mappings_are_edited() {
+        let dir = TempDir::with_files(
+            "edit",
+            &[
+                (
+                    "system/mappings.toml",
+                    "[gs]\ncommand = \"git status\"\ndescription = \"Status\"\n",
+                ),
+                ("user/mappings.toml", "[gs]\nexecute = true\n"),
+                ("user/mappings/a.toml", "[gc]\ncommand = \"git commit\"\n"),
+                ("user/mappings/b.toml", "[gc]\nexecute = true\n"),
+            ],
+        );
+        let (system, user) = (dir.path().join("system"), dir.path().join("user"));
+        let config_dirs = [system.clone(), user.clone()];
+        let mappings = Mappings::load(&config_dirs, UnknownKeys::Error).unwrap();
+
+        // 'gs' is partly defined system-wide, so neither file is touched.
+        let err = remove_mapping(&mappings, "gs", &user).unwrap_err();
+        assert!(err.to_string().contains("system"), "{err}");
+        assert!(rename_mapping(&mappings, "gs", "gt", &user).is_err());
+        assert!(
+            MappingFile::open(&user.join("mappings.toml"), &mappings)
+                .unwrap()
+                .contains("gs")
+        );
+
+        // 'gc' is moved and removed in both files defining it.
+        let paths = rename_mapping(&mappings, "gc", "gd", &user).unwrap();
+        assert_eq!(
+            paths,
+            [user.join("mappings/a.toml"), user.join("mappings/b.toml")]
+        );
+        let mappings = Mappings::load(&config_dirs, UnknownKeys::Error).unwrap();
+        assert!(mappings.match_sequence("gd").unwrap().execute);
+        remove_mapping(&mappings, "gd", &user).unwrap();
+        let mappings = Mappings::load(&config_dirs, UnknownKeys::Error).unwrap();
+        assert!(
+            mappings
+                .match_sequence_including_unavailable("gd")
+                .is_none()
+        );
+    }
+}
@@ -4,12 +4,26 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use color_eyre::eyre::{Result, eyre};
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::ui::table;
+use crate::{
+    layers::{self, Layer, Merged, Origin},
+    toml_file::{self, UnknownKeys},
+    ui::{color::ColorSupport, table, theme::ThemeColor},
+};
 
-#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    clap::ValueEnum,
+    schemars::JsonSchema,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum InsertType {
     /// Replace the current prompt with the mapped command.
     #[default]
@@ -29,10 +43,14 @@ pub enum InsertType {
 }
 
 /// Represents a user-defined key sequence to command mapping with additional metadata.
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, schemars::JsonSchema, serde::Serialize, serde::Deserialize)]
 pub struct Mapping {
+    /// The command to insert or execute.
+    // Defaulted so that an overlay can change other fields of a mapping defined in another layer.
+    #[serde(default)]
     pub command: String,
 
+    /// Description shown in the panel and the mapping list.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
@@ -49,8 +67,34 @@ pub struct Mapping {
     #[serde(default, skip_serializing_if = "is_false")]
     pub execute: bool,
 
+    /// Programs that have to be in PATH for this mapping to be available.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+
+    /// Icon shown in front of the description in the panel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
+    /// Color of the icon and description in the panel, either the name of a theme color
+    /// like `"text_highlight_primary"` or a literal color like `"red"` or `"#f38ba8"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<ThemeColor>,
+
+    /// Position in the panel with `sort = "custom"`. Lower values come first,
+    /// mappings without an order come last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+
     #[serde(skip)]
     pub source_file: Option<std::path::PathBuf>,
+
+    /// Every file defining any field of this mapping, in the order they were merged.
+    #[serde(skip)]
+    pub defined_in: Vec<std::path::PathBuf>,
+
+    /// Position of the first definition of this mapping across all loaded files.
+    #[serde(skip)]
+    pub definition_index: Option<usize>,
 }
 
 impl Default for Mapping {
@@ -61,7 +105,13 @@ impl Default for Mapping {
             insert_type: InsertType::Replace,
             evaluate: false,
             execute: false,
+            requires: Vec::new(),
+            icon: None,
+            color: None,
+            order: None,
             source_file: None,
+            defined_in: Vec::new(),
+            definition_index: None,
         }
     }
 }
@@ -85,14 +135,89 @@ impl Mapping {
 
 pub enum MatchType<'a> {
     Exact(&'a Mapping),
-    Prefix(usize),
+    Prefix {
+        count: usize,
+        group: Option<&'a Group>,
+    },
     None,
 }
 
-#[derive(Serialize, Deserialize)]
+/// How a prefix group is shown in the panel.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Group {
+    /// Icon shown in front of the group's label.
+    pub icon: Option<String>,
+
+    /// Color of the icon and label, either the name of a theme color or a literal color.
+    pub color: Option<ThemeColor>,
+
+    /// Position in the panel with `sort = "custom"`. Defaults to the lowest order of the
+    /// mappings in the group.
+    pub order: Option<i64>,
+}
+
+impl Group {
+    /// Overrides the fields set in `other`.
+    fn merge(&mut self, other: Group) {
+        self.icon = other.icon.or(self.icon.take());
+        self.color = other.color.or(self.color);
+        self.order = other.order.or(self.order);
+    }
+}
+
+/// Order of the continuations shown in the panel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// By character, so `A` comes before `a` and digits before letters.
+    #[default]
+    Alphabetical,
+    /// Alphabetically, ignoring case.
+    CaseInsensitive,
+    /// Prefixes before mappings, each alphabetically.
+    PrefixesFirst,
+    /// Mappings before prefixes, each alphabetically.
+    LeavesFirst,
+    /// In the order the mappings are defined in, following includes.
+    DefinitionOrder,
+    /// By the `order` of mappings and groups, then alphabetically.
+    Custom,
+}
+
+impl SortOrder {
+    /// Whether prefixes and mappings end up in two separate blocks.
+    pub fn splits_groups(self) -> bool {
+        matches!(self, SortOrder::PrefixesFirst | SortOrder::LeavesFirst)
+    }
+}
+
+/// Key sequences mapped to the commands they trigger.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct Mappings {
     mappings: HashMap<String, Mapping>,
+
+    /// Mappings whose required programs are missing. They are neither matched nor shown.
+    #[serde(skip)]
+    unavailable: HashMap<String, Mapping>,
+
+    /// Prefix groups keyed by their sequence.
+    #[serde(skip)]
+    groups: HashMap<String, Group>,
+
+    /// The files mappings were loaded from.
+    #[serde(skip)]
+    files: HashMap<PathBuf, SourceFile>,
+}
+
+/// Where a loaded mapping file came from.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SourceFile {
+    /// The config directory whose mapping files included this one.
+    pub config_dir: PathBuf,
+    /// The prefix the file's mappings are mounted under, including those of including files.
+    pub prefix: String,
 }
 
 impl Default for Mappings {
@@ -162,42 +287,114 @@ impl Default for Mappings {
                 command: " | xclip -selection clipboard".into(),
                 description: Some("Append copy to clipboard".into()),
                 insert_type: InsertType::Append,
+                requires: vec!["xclip".into()],
                 ..Default::default()
             },
         );
-        Self { mappings }
+        Self {
+            mappings,
+            unavailable: HashMap::new(),
+            groups: HashMap::new(),
+            files: HashMap::new(),
+        }
     }
 }
 
 impl Mappings {
-    pub fn load(config_dir: &Path) -> Result<Self> {
-        let mut merged = HashMap::new();
-
-        // 1. Load main mappings.toml
-        let main_file = config_dir.join("mappings.toml");
-        if main_file.exists() {
-            let contents = fs::read_to_string(&main_file)?;
-            let mappings: Mappings = toml::from_str(&contents)?;
-            for (key, mut mapping) in mappings.mappings {
-                mapping.source_file = Some(main_file.clone());
-                merged.insert(key, mapping);
+    /// Loads the mappings from the given config directories, lowest priority first.
+    ///
+    /// Within each directory, `mappings.toml` and its host specific variant are read before
+    /// the files in `mappings/`. Files pulled in via `include` are read before the including file.
+    /// Mappings with the same key sequence are merged field by field.
+    /// Mappings requiring programs that are not in PATH are set aside as unavailable.
+    pub fn load(config_dirs: &[PathBuf], unknown_keys: UnknownKeys) -> Result<Self> {
+        let mut paths = Vec::new();
+        for config_dir in config_dirs {
+            paths.push((config_dir, config_dir.join("mappings.toml")));
+            if let Some(host_file) = layers::host_specific("mappings.toml") {
+                paths.push((config_dir, config_dir.join(host_file)));
+            }
+            let mappings_dir = config_dir.join("mappings");
+            if mappings_dir.is_dir() {
+                for path in collect_toml_files(&mappings_dir)? {
+                    paths.push((config_dir, path));
+                }
             }
         }
 
-        // 2. Load recursively from mappings/ directory
-        let mappings_dir = config_dir.join("mappings");
-        if mappings_dir.exists() && mappings_dir.is_dir() {
-            for path in collect_toml_files(&mappings_dir)? {
-                let contents = fs::read_to_string(&path)?;
-                let mappings: Mappings = toml::from_str(&contents)?;
-                for (key, mut mapping) in mappings.mappings {
-                    mapping.source_file = Some(path.clone());
-                    merged.insert(key, mapping);
+        let mut layers = Vec::new();
+        let mut groups = HashMap::new();
+        let mut prefixes = HashMap::new();
+        let mut files = HashMap::new();
+        for (config_dir, path) in paths {
+            if path.is_file() {
+                let first = layers.len();
+                read_mapping_file(
+                    &path,
+                    "",
+                    &[],
+                    &mut Vec::new(),
+                    &mut layers,
+                    &mut groups,
+                    &mut prefixes,
+                )?;
+                for layer in &layers[first..] {
+                    files.insert(
+                        layer.path.clone(),
+                        SourceFile {
+                            config_dir: config_dir.clone(),
+                            prefix: prefixes.remove(&layer.path).unwrap_or_default(),
+                        },
+                    );
                 }
             }
         }
+        let unknown: Vec<String> = layers
+            .iter()
+            .flat_map(|layer| layer.unknown.iter().cloned())
+            .collect();
+        toml_file::report_unknown_keys(&unknown, unknown_keys)?;
+
+        let mut merged = Merged::new(toml::Table::new(), Origin::Default);
+        let mut source_files: HashMap<&String, Vec<PathBuf>> = HashMap::new();
+        for layer in &layers {
+            merged.merge(layer);
+            for key in layer.table.keys() {
+                source_files
+                    .entry(key)
+                    .or_default()
+                    .push(layer.path.clone());
+            }
+        }
+
+        // Merged tables keep the position of the first definition of every key.
+        let definition_indices: HashMap<&String, usize> = merged
+            .table
+            .keys()
+            .enumerate()
+            .map(|(index, key)| (key, index))
+            .collect();
+
+        let (mut final_mappings, _): (Mappings, _) = merged.deserialize(&layers)?;
+        for (key, mapping) in &mut final_mappings.mappings {
+            mapping.defined_in = source_files.remove(key).unwrap_or_default();
+            mapping.source_file = mapping.defined_in.last().cloned();
+            mapping.definition_index = definition_indices.get(key).copied();
+        }
+
+        let mut found = HashMap::new();
+        let (available, unavailable) = final_mappings.mappings.drain().partition(|(_, mapping)| {
+            mapping.requires.iter().all(|program| {
+                *found
+                    .entry(program.clone())
+                    .or_insert_with(|| is_on_path(program))
+            })
+        });
+        final_mappings.mappings = available;
+        final_mappings.unavailable = unavailable;
+        final_mappings.groups = groups;
+        final_mappings.files = files;
 
-        let final_mappings = Mappings { mappings: merged };
         final_mappings.validate()?;
         Ok(final_mappings)
     }
@@ -213,18 +410,63 @@ impl Mappings {
         Ok(())
     }
 
+    /// Replaces the literal colors of mappings and groups by the closest ones the terminal can display.
+    pub fn approximate_colors(&mut self, support: ColorSupport) {
+        let mapping_colors = self.mappings.values_mut().map(|mapping| &mut mapping.color);
+        let group_colors = self.groups.values_mut().map(|group| &mut group.color);
+        for color in mapping_colors.chain(group_colors).flatten() {
+            *color = color.approximate(support);
+        }
+    }
+
+    /// Iterates over all key sequences and their mappings in arbitrary order.
+    /// Unavailable mappings are left out.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Mapping)> {
+        self.mappings.iter()
+    }
+
     /// Returns an exact match for a given sequence, if one exists.
     pub fn match_sequence(&self, sequence: &str) -> Option<&Mapping> {
         self.mappings.get(sequence)
     }
 
+    /// Like [`Mappings::match_sequence`], but also considers unavailable mappings.
+    pub(crate) fn match_sequence_including_unavailable(&self, sequence: &str) -> Option<&Mapping> {
+        self.mappings
+            .get(sequence)
+            .or_else(|| self.unavailable.get(sequence))
+    }
+
+    /// Adds or replaces the mapping for a sequence without validating it.
+    pub(crate) fn insert(&mut self, sequence: String, mapping: Mapping) {
+        self.mappings.insert(sequence, mapping);
+    }
+
+    /// Removes the mapping for a sequence, available or not, returning it if it existed.
+    pub(crate) fn remove(&mut self, sequence: &str) -> Option<Mapping> {
+        self.mappings
+            .remove(sequence)
+            .or_else(|| self.unavailable.remove(sequence))
+    }
+
     /// Returns true if any mapping begins with the given sequence.
     pub fn has_partial_match(&self, seq: &str) -> bool {
         self.mappings.keys().any(|k| k.starts_with(seq))
     }
 
-    /// Returns a set of next possible keys given a partial sequence.
-    pub fn next_possible_keys(&self, sequence: &str) -> BTreeSet<String> {
+    /// Returns an existing sequence that would conflict with a new mapping for `sequence`,
+    /// i.e. one that is equal to it, a prefix of it or prefixed by it.
+    /// Unavailable mappings count as well, since their programs may exist on other hosts.
+    pub fn find_conflict(&self, sequence: &str) -> Option<&str> {
+        self.mappings
+            .keys()
+            .chain(self.unavailable.keys())
+            .find(|key| key.starts_with(sequence) || sequence.starts_with(key.as_str()))
+            .map(|key| key.as_str())
+    }
+
+    /// Returns the next possible keys given a partial sequence, in the given order.
+    pub fn next_possible_keys(&self, sequence: &str, sort: SortOrder) -> Vec<String> {
         let mut next_keys = BTreeSet::new();
 
         for key in self.mappings.keys() {
@@ -236,9 +478,59 @@ impl Mappings {
             }
         }
 
+        // The sorts are stable, so ties stay in alphabetical order.
+        let mut next_keys: Vec<String> = next_keys.into_iter().collect();
+        let is_prefix = |key: &String| !self.mappings.contains_key(&format!("{sequence}{key}"));
+        match sort {
+            SortOrder::Alphabetical => {}
+            SortOrder::CaseInsensitive => next_keys.sort_by_cached_key(|key| key.to_lowercase()),
+            SortOrder::PrefixesFirst => next_keys.sort_by_cached_key(|key| !is_prefix(key)),
+            SortOrder::LeavesFirst => next_keys.sort_by_cached_key(is_prefix),
+            SortOrder::DefinitionOrder => next_keys.sort_by_cached_key(|key| {
+                self.below(&format!("{sequence}{key}"))
+                    .filter_map(|mapping| mapping.definition_index)
+                    .min()
+                    .unwrap_or(usize::MAX)
+            }),
+            SortOrder::Custom => next_keys.sort_by_cached_key(|key| {
+                let full_sequence = format!("{sequence}{key}");
+                let order = self
+                    .groups
+                    .get(&full_sequence)
+                    .and_then(|group| group.order)
+                    .or_else(|| {
+                        self.below(&full_sequence)
+                            .filter_map(|mapping| mapping.order)
+                            .min()
+                    });
+                // Entries with an order come first.
+                (order.is_none(), order)
+            }),
+        }
+
         next_keys
     }
 
+    /// Returns the only mapping whose sequence starts with `sequence`, if there is exactly one.
+    pub fn unique_match(&self, sequence: &str) -> Option<(&str, &Mapping)> {
+        let mut matches = self
+            .mappings
+            .iter()
+            .filter(|(key, _)| key.starts_with(sequence));
+        match (matches.next(), matches.next()) {
+            (Some((key, mapping)), None) => Some((key.as_str(), mapping)),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the mappings whose sequence starts with `sequence`.
+    fn below<'a>(&'a self, sequence: &'a str) -> impl Iterator<Item = &'a Mapping> + 'a {
+        self.mappings
+            .iter()
+            .filter(move |(key, _)| key.starts_with(sequence))
+            .map(|(_, mapping)| mapping)
+    }
+
     /// Resolves a sequence into either an exact match or a number of possible continuations.
     pub fn match_partial_sequence(&self, sequence: &str) -> MatchType<'_> {
         if let Some(mapping) = self.mappings.get(sequence) {
@@ -253,22 +545,29 @@ impl Mappings {
         }
 
         if count > 0 {
-            MatchType::Prefix(count)
+            MatchType::Prefix {
+                count,
+                group: self.groups.get(sequence),
+            }
         } else {
             MatchType::None
         }
     }
 
-    fn validate(&self) -> Result<()> {
-        let keys: Vec<&String> = self.mappings.keys().collect();
+    /// Returns where the mapping file at `path` was loaded from.
+    pub(crate) fn source_file(&self, path: &Path) -> Option<&SourceFile> {
+        self.files.get(path)
+    }
+
+    /// Checks all mappings, including unavailable ones: the same files are loaded on hosts
+    /// where their programs exist.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let all: Vec<(&String, &Mapping)> = self.mappings.iter().chain(&self.unavailable).collect();
 
         // Validate that no mappings overlap or are prefixes of each other.
-        for (i, key1) in keys.iter().enumerate() {
-            for key2 in keys.iter().skip(i + 1) {
+        for (i, (key1, mapping1)) in all.iter().enumerate() {
+            for (key2, mapping2) in all.iter().skip(i + 1) {
                 if key1.starts_with(*key2) || key2.starts_with(*key1) {
-                    let mapping1 = &self.mappings[*key1];
-                    let mapping2 = &self.mappings[*key2];
-
                     let file1 = mapping1
                         .source_file
                         .as_ref()
@@ -288,8 +587,20 @@ impl Mappings {
             }
         }
 
+        for (key, mapping) in &all {
+            if mapping.command.is_empty() {
+                let file = mapping
+                    .source_file
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "unknown source".to_string());
+
+                return Err(eyre!("Mapping '{key}' (from {file}) has no command"));
+            }
+        }
+
         // Make sure that "Surround" type mappings contain "#COMMAND" in their command
-        for mapping in self.mappings.values() {
+        for (_, mapping) in &all {
             if mapping.insert_type == InsertType::Surround && !mapping.command.contains("#COMMAND")
             {
                 let file = mapping
@@ -308,7 +619,17 @@ impl Mappings {
         Ok(())
     }
 
-    pub fn render_table(&self) -> String {
+    /// Renders all available mappings as a table.
+    /// With `include_unavailable`, mappings whose required programs are missing are listed too,
+    /// along with the programs they are missing.
+    pub fn render_table(&self, include_unavailable: bool) -> String {
+        let mut rows: Vec<_> = self.mappings.iter().collect();
+        if include_unavailable {
+            rows.extend(&self.unavailable);
+        }
+        rows.sort_by_key(|(key, _)| *key); // Sorts alphabetically (lexicographically)
+
+        let has_icons = rows.iter().any(|(_, mapping)| mapping.icon.is_some());
         let layout = table::ColumnLayout {
             sequence: 8,
             command: 30,
@@ -317,18 +638,22 @@ impl Mappings {
             execute: 9,
             description: 40,
             source: 30,
+            missing: include_unavailable.then_some(20),
+            icon: has_icons.then_some(4),
         };
 
         let mut table = String::new();
         table.push_str(&table::render_header(&layout));
         table.push_str(&table::render_separator(&layout));
 
-        let mut keys: Vec<_> = self.mappings.keys().collect();
-        keys.sort(); // Sorts alphabetically (lexicographically)
-
-        for key in keys {
-            let mapping = &self.mappings[key];
-            table.push_str(&table::render_row(&layout, key, mapping));
+        for (key, mapping) in rows {
+            let missing: Vec<String> = mapping
+                .requires
+                .iter()
+                .filter(|program| !is_on_path(program))
+                .cloned()
+                .collect();
+            table.push_str(&table::render_row(&layout, key, mapping, &missing));
         }
 
         table
@@ -342,6 +667,239 @@ fn is_false(b: &bool) -> bool {
     !*b
 }
 
+/// Settings of a mapping file that don't describe a mapping.
+#[derive(Default, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Meta {
+    /// Key sequence prepended to all mappings of this file and the files it includes.
+    pub prefix: String,
+
+    /// Programs required by all mappings of this file and the files it includes.
+    pub requires: Vec<String>,
+
+    /// Icons and colors of prefix groups, keyed by their sequence relative to `prefix`.
+    pub groups: HashMap<String, Group>,
+}
+
+/// Top-level keys of mapping files that don't hold a mapping.
+pub(crate) const RESERVED_KEYS: [&str; 2] = ["include", "meta"];
+
+/// The contents of a single mapping file: mappings keyed by their sequence,
+/// plus the reserved `include` and `meta` keys.
+#[derive(Default, JsonSchema)]
+#[schemars(rename = "MappingFile")]
+pub struct MappingFileContents {
+    /// Files or directories to load mappings from, relative to this file.
+    /// `~` and environment variables like `$HOME` are expanded.
+    #[schemars(default)]
+    pub include: Vec<String>,
+
+    #[schemars(default)]
+    pub meta: Meta,
+
+    #[schemars(flatten)]
+    pub mappings: HashMap<String, Mapping>,
+}
+
+impl<'de> Deserialize<'de> for MappingFileContents {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = MappingFileContents;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a table of mappings")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut contents = MappingFileContents::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "include" => contents.include = map.next_value()?,
+                        "meta" => contents.meta = map.next_value()?,
+                        _ => {
+                            let mapping = map.next_value()?;
+                            contents.mappings.insert(key, mapping);
+                        }
+                    }
+                }
+                Ok(contents)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// Reads a mapping file and everything it includes, mounting its mappings under `prefix`
+/// and its own `[meta] prefix`. Programs in `requires` and `[meta] requires` are added to the
+/// requirements of every mapping. Included files end up before the including file in `layers`,
+/// and their `[meta.groups]` are overridden by those of the including file.
+/// The full prefix of every file read is recorded in `prefixes`.
+fn read_mapping_file(
+    path: &Path,
+    prefix: &str,
+    requires: &[String],
+    stack: &mut Vec<PathBuf>,
+    layers: &mut Vec<Layer>,
+    groups: &mut HashMap<String, Group>,
+    prefixes: &mut HashMap<PathBuf, String>,
+) -> Result<()> {
+    let canonical =
+        fs::canonicalize(path).wrap_err_with(|| format!("Failed to read {:?}", path))?;
+    if stack.contains(&canonical) {
+        let cycle: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(eyre!("Include cycle: {}", cycle.join(" -> ")));
+    }
+
+    let Some(mut layer) = Layer::read::<MappingFileContents>(path)? else {
+        return Err(eyre!("Mapping file {:?} not found", path));
+    };
+    let include: Vec<String> = match layer.table.remove("include") {
+        Some(include) => include.try_into()?,
+        None => Vec::new(),
+    };
+    let meta: Meta = match layer.table.remove("meta") {
+        Some(meta) => meta.try_into()?,
+        None => Meta::default(),
+    };
+    let prefix = format!("{prefix}{}", meta.prefix);
+    let requires: Vec<String> = requires.iter().chain(&meta.requires).cloned().collect();
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    for entry in include {
+        let included = expand_path(&entry, base_dir, |name| std::env::var(name).ok())
+            .wrap_err_with(|| format!("Invalid include '{entry}' in {:?}", path))?;
+        let files = if included.is_dir() {
+            collect_toml_files(&included)?
+        } else if included.is_file() {
+            vec![included]
+        } else {
+            return Err(eyre!(
+                "Included path {:?} not found (from {:?})",
+                included,
+                path
+            ));
+        };
+        for file in files {
+            read_mapping_file(&file, &prefix, &requires, stack, layers, groups, prefixes)?;
+        }
+    }
+    stack.pop();
+
+    for (sequence, group) in meta.groups {
+        groups
+            .entry(format!("{prefix}{sequence}"))
+            .or_default()
+            .merge(group);
+    }
+
+    if !requires.is_empty() {
+        for (_, value) in layer.table.iter_mut() {
+            let Some(mapping) = value.as_table_mut() else {
+                continue;
+            };
+            let own = mapping
+                .remove("requires")
+                .map(toml::Value::try_into::<Vec<String>>)
+                .transpose()?
+                .unwrap_or_default();
+            let mut combined = requires.clone();
+            combined.extend(
+                own.into_iter()
+                    .filter(|program| !requires.contains(program)),
+            );
+            mapping.insert("requires".into(), toml::Value::try_from(combined)?);
+        }
+    }
+
+    prefixes.insert(layer.path.clone(), prefix.clone());
+    if !prefix.is_empty() {
+        layer.table = std::mem::take(&mut layer.table)
+            .into_iter()
+            .map(|(key, value)| (format!("{prefix}{key}"), value))
+            .collect();
+    }
+    layers.push(layer);
+    Ok(())
+}
+
+/// Expands a leading `~` and environment variables (`$VAR` or `${VAR}`) in `path`,
+/// looking up their values with `var`. Relative paths are interpreted relative to `base_dir`.
+fn expand_path(
+    path: &str,
+    base_dir: &Path,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<PathBuf> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if let Some(after_tilde) = rest.strip_prefix('~')
+        && (after_tilde.is_empty() || after_tilde.starts_with('/'))
+    {
+        let home = directories::BaseDirs::new()
+            .ok_or_else(|| eyre!("Could not determine the home directory"))?;
+        expanded.push_str(&home.home_dir().to_string_lossy());
+        rest = after_tilde;
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| eyre!("Unterminated '${{' in '{path}'"))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            let value =
+                var(name).ok_or_else(|| eyre!("Environment variable '{name}' is not set"))?;
+            expanded.push_str(&value);
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+
+    Ok(base_dir.join(expanded))
+}
+
+/// Returns whether `program` is an executable file in one of the directories in PATH.
+/// Programs given as a path are checked directly.
+fn is_on_path(program: &str) -> bool {
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program)))
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
 fn collect_toml_files(dir: &Path) -> Result<Vec<PathBuf>> {
     let mut result = Vec::new();
     for entry in fs::read_dir(dir)? {
@@ -353,12 +911,14 @@ fn collect_toml_files(dir: &Path) -> Result<Vec<PathBuf>> {
             result.push(path);
         }
     }
+    result.sort();
     Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::TempDir;
 
     #[test]
     fn test_format_replace_no_flags() {
@@ -437,7 +997,7 @@ mod tests {
     #[test]
     fn test_render_table_contains_mapping_keys() {
         let mappings = Mappings::default();
-        let table = mappings.render_table();
+        let table = mappings.render_table(false);
         assert!(table.contains("gs"));
         assert!(table.contains("git status"));
         assert!(table.contains("Description"));
@@ -461,7 +1021,12 @@ mod tests {
             },
         );
 
-        Mappings { mappings }
+        Mappings {
+            mappings,
+            unavailable: HashMap::new(),
+            groups: HashMap::new(),
+            files: HashMap::new(),
+        }
     }
 
     #[test]
@@ -514,4 +1079,188 @@ mod tests {
         assert!(mappings.has_partial_match("g"));
         assert!(!mappings.has_partial_match("x"));
     }
+
+    #[test]
+    fn test_find_conflict() {
+        let mappings = test_mappings();
+
+        assert_eq!(mappings.find_conflict("g"), Some("gs"));
+        assert_eq!(mappings.find_conflict("gs"), Some("gs"));
+        assert_eq!(mappings.find_conflict("sx"), Some("s"));
+        assert_eq!(mappings.find_conflict("gc"), None);
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let base = Path::new("/config");
+        let var = |name: &str| (name == "PACKS").then(|| "/packs".to_string());
+
+        assert_eq!(
+            expand_path("git.toml", base, var).unwrap(),
+            PathBuf::from("/config/git.toml")
+        );
+        assert_eq!(
+            expand_path("$PACKS/git.toml", base, var).unwrap(),
+            PathBuf::from("/packs/git.toml")
+        );
+        assert_eq!(
+            expand_path("${PACKS}x/git.toml", base, var).unwrap(),
+            PathBuf::from("/packsx/git.toml")
+        );
+        assert!(expand_path("$UNSET/git.toml", base, var).is_err());
+        assert!(expand_path("~/git.toml", base, var).unwrap().is_absolute());
+    }
+
+    #[test]
+    fn test_include_with_prefix() {
+        let dir = TempDir::with_files(
+            "include",
+            &[
+                (
+                    "mappings.toml",
+                    "include = [\"packs\"]\n\n[gc]\ncommand = \"git commit\"\n",
+                ),
+                (
+                    "packs/git.toml",
+                    "[meta]\nprefix = \"g\"\n\n[s]\ncommand = \"git status\"\n",
+                ),
+            ],
+        );
+        let config_dirs = [dir.path().to_path_buf()];
+
+        let mappings = Mappings::load(&config_dirs, UnknownKeys::Error).unwrap();
+        let mapping = mappings.match_sequence("gs").unwrap();
+        assert_eq!(mapping.command, "git status");
+        assert_eq!(mapping.source_file, Some(dir.path().join("packs/git.toml")));
+        assert!(mappings.match_sequence("gc").is_some());
+
+        // Conflicts are checked against the mounted sequences
+        dir.write(
+            "mappings.toml",
+            "include = [\"packs\"]\n\n[g]\ncommand = \"git\"\n",
+        );
+        let Err(err) = Mappings::load(&config_dirs, UnknownKeys::Error) else {
+            panic!("expected a conflict between 'g' and 'gs'");
+        };
+        assert!(
+            err.to_string().contains("Conflicting key sequence"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_requires_hides_missing_programs() {
+        let dir = TempDir::with_files(
+            "requires",
+            &[(
+                "mappings.toml",
+                "[meta]\nrequires = [\"sh\"]\n\n\
+                 [a]\ncommand = \"a\"\n\n\
+                 [b]\ncommand = \"b\"\nrequires = [\"leadr-nope\"]\n",
+            )],
+        );
+        let config_dirs = [dir.path().to_path_buf()];
+
+        let mappings = Mappings::load(&config_dirs, UnknownKeys::Error).unwrap();
+        assert_eq!(mappings.match_sequence("a").unwrap().requires, vec!["sh"]);
+        assert!(mappings.match_sequence("b").is_none());
+        assert!(!mappings.has_partial_match("b"));
+
+        let b = mappings.match_sequence_including_unavailable("b").unwrap();
+        assert_eq!(b.requires, vec!["sh", "leadr-nope"]);
+        assert!(!mappings.render_table(false).contains("leadr-nope"));
+        assert!(mappings.render_table(true).contains("leadr-nope"));
+
+        // Unavailable mappings still conflict, since other hosts load them.
+        assert_eq!(mappings.find_conflict("bc"), Some("b"));
+        dir.write(
+            "mappings.toml",
+            "[b]\ncommand = \"b\"\nrequires = [\"leadr-nope\"]\n\n[bc]\ncommand = \"bc\"\n",
+        );
+        let Err(err) = Mappings::load(&config_dirs, UnknownKeys::Error) else {
+            panic!("expected a conflict between 'b' and 'bc'");
+        };
+        assert!(
+            err.to_string().contains("Conflicting key sequence"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_icons_colors_and_groups() {
+        use crate::ui::{
+            color::{Color, NamedColor},
+            theme::Slot,
+        };
+
+        let dir = TempDir::with_files(
+            "groups",
+            &[
+                (
+                    "mappings.toml",
+                    "include = [\"packs\"]\n\n\
+                     [meta.groups.g]\ncolor = \"text_highlight_primary\"\n\n\
+                     [x]\ncommand = \"rm -rf\"\nicon = \"!\"\ncolor = \"red\"\n",
+                ),
+                (
+                    "packs/git.toml",
+                    "[meta]\nprefix = \"g\"\n\n\
+                     [meta.groups.\"\"]\nicon = \"G\"\ncolor = \"blue\"\n\n\
+                     [s]\ncommand = \"git status\"\n",
+                ),
+            ],
+        );
+
+        let mappings = Mappings::load(&[dir.path().to_path_buf()], UnknownKeys::Error).unwrap();
+        let x = mappings.match_sequence("x").unwrap();
+        assert_eq!(x.icon.as_deref(), Some("!"));
+        assert_eq!(
+            x.color,
+            Some(ThemeColor::Literal(Color::Named(NamedColor::Red)))
+        );
+
+        let MatchType::Prefix {
+            count: 1,
+            group: Some(group),
+        } = mappings.match_partial_sequence("g")
+        else {
+            panic!("'g' is not a prefix group");
+        };
+        assert_eq!(group.icon.as_deref(), Some("G"));
+        // The including file overrides the group of the included one.
+        assert_eq!(
+            group.color,
+            Some(ThemeColor::Slot(Slot::TextHighlightPrimary))
+        );
+
+        let table = mappings.render_table(false);
+        assert!(table.starts_with("Icon"), "{table}");
+    }
+
+    #[test]
+    fn test_sort_orders() {
+        let dir = TempDir::with_files(
+            "sort",
+            &[(
+                "mappings.toml",
+                "[meta.groups.g]\norder = 1\n\n\
+                 [z]\ncommand = \"z\"\norder = 2\n\n\
+                 [gs]\ncommand = \"git status\"\n\n\
+                 [B]\ncommand = \"b\"\n\n\
+                 [a]\ncommand = \"a\"\n\n\
+                 [1]\ncommand = \"1\"\n\n\
+                 [dd]\ncommand = \"dd\"\norder = 0\n",
+            )],
+        );
+
+        let mappings = Mappings::load(&[dir.path().to_path_buf()], UnknownKeys::Error).unwrap();
+        let keys = |sort| mappings.next_possible_keys("", sort).join("");
+        assert_eq!(keys(SortOrder::Alphabetical), "1Badgz");
+        assert_eq!(keys(SortOrder::CaseInsensitive), "1aBdgz");
+        assert_eq!(keys(SortOrder::PrefixesFirst), "dg1Baz");
+        assert_eq!(keys(SortOrder::LeavesFirst), "1Bazdg");
+        assert_eq!(keys(SortOrder::DefinitionOrder), "zgBa1d");
+        // `d` takes the order of `dd`, entries without an order come last.
+        assert_eq!(keys(SortOrder::Custom), "dgz1Ba");
+    }
 }
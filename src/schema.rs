@@ -0,0 +1,52 @@
+//! JSON Schemas for the configuration files.
+//!
+//! Editors with TOML language support (e.g. taplo or even-better-toml) can use these
+//! to validate and autocomplete `config.toml`, mapping files and theme files.
+
+use color_eyre::eyre::Result;
+
+use crate::{Config, mappings::MappingFileContents, ui::theme::ThemeFile};
+
+/// The kinds of files leadr can generate a schema for.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum SchemaKind {
+    /// The main `config.toml`.
+    Config,
+    /// `mappings.toml` and files in the `mappings/` directory.
+    Mappings,
+    /// Files in the `themes/` directory.
+    Theme,
+}
+
+/// Generates the pretty-printed JSON Schema for a kind of file.
+pub fn generate(kind: SchemaKind) -> Result<String> {
+    let schema = match kind {
+        SchemaKind::Config => schemars::schema_for!(Config),
+        SchemaKind::Mappings => schemars::schema_for!(MappingFileContents),
+        SchemaKind::Theme => schemars::schema_for!(ThemeFile),
+    };
+    Ok(serde_json::to_string_pretty(&schema)? + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The shipped schemas have to be regenerated whenever the config structs change:
+    /// `leadr --schema <kind> > schemas/<kind>.schema.json`
+    #[test]
+    fn test_shipped_schemas_are_up_to_date() {
+        assert_eq!(
+            generate(SchemaKind::Config).unwrap(),
+            include_str!("../schemas/config.schema.json")
+        );
+        assert_eq!(
+            generate(SchemaKind::Mappings).unwrap(),
+            include_str!("../schemas/mappings.schema.json")
+        );
+        assert_eq!(
+            generate(SchemaKind::Theme).unwrap(),
+            include_str!("../schemas/theme.schema.json")
+        );
+    }
+}
@@ -1,14 +1,24 @@
-use std::time::{Duration, Instant};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-use color_eyre::eyre::Result;
-use crossterm::event::{Event, KeyCode, KeyEvent, poll, read};
+use color_eyre::eyre::{Result, eyre};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind, poll, read,
+};
 
-use crate::{Config, Mappings, Panel, RawModeGuard, Theme, ui::prompt};
+use crate::{
+    Config, Mapping, Mappings, MouseCaptureGuard, Panel, RawModeGuard, Theme, mapping_file,
+    ui::{form::SaveForm, panel::Direction, prompt},
+};
 
 pub enum SessionResult {
     Command(String),
     Cancelled,
     NoMatch,
+    /// The current command line was saved as a new mapping.
+    Saved,
 }
 
 /// Handles keyboard input and matches sequences to mapped commands.
@@ -16,84 +26,345 @@ pub struct LeadrSession {
     mappings: Mappings,
     config: Config,
     theme: Theme,
+    config_dir: PathBuf,
     sequence: String,
+    form: Option<SaveForm>,
 }
 
 impl LeadrSession {
-    pub fn new(mappings: Mappings, config: Config, theme: Theme) -> Self {
+    pub fn new(mappings: Mappings, config: Config, theme: Theme, config_dir: PathBuf) -> Self {
         LeadrSession {
             mappings,
             config,
             theme,
+            config_dir,
             sequence: String::new(),
+            form: None,
         }
     }
 
     /// Runs the input loop, capturing key events and returning when a mapping is matched,
     /// canceled, or an invalid sequence is entered.
     pub fn run(&mut self) -> Result<SessionResult> {
+        let save_key = self.config.save_key_event()?;
+        let (next_page_key, previous_page_key) = self.config.page_key_events()?;
+        let (cancel_key, back_key) = self.config.cancel_and_back_key_events()?;
         let _raw_mode_guard = RawModeGuard::new()?;
         let start_time = Instant::now();
-        let mut panel: Option<Panel> = None;
 
         // Cosmetically fix the prompt line disappearing while leadr is active.
-        let mut prompt_guard = prompt::PromptGuard::try_new();
-        if let Ok(ref mut guard) = prompt_guard
-            && self.config.redraw_prompt_line
-        {
-            guard.redraw()?;
-        }
+        let mut prompt_guard = prompt::PromptGuard::try_new(self.theme.monochrome).ok();
+        self.redraw_prompt_line(&mut prompt_guard)?;
+
+        // Declared after the prompt guard so the panel is cleared first
+        // and the prompt line is back in place when the guard clears it.
+        let mut panel: Option<Panel> = None;
+        // Only captured while the panel is shown, so the terminal's own selection keeps working
+        // until then. Released before raw mode is disabled.
+        let mut _mouse_capture_guard: Option<MouseCaptureGuard> = None;
 
         loop {
             let timeout_reached = start_time.elapsed() >= self.config.panel.delay;
             if self.config.panel.enabled && panel.is_none() && timeout_reached {
-                panel = self.try_new_panel()?;
+                panel = self.try_new_panel(&mut prompt_guard)?;
+                _mouse_capture_guard = self.capture_mouse(&panel);
             }
 
-            if poll(Duration::from_millis(50))?
-                && let Event::Key(KeyEvent {
+            if poll(Duration::from_millis(50))? {
+                let event = read()?;
+                if let Event::Resize(..) = event {
+                    self.handle_resize(&mut panel, &mut prompt_guard)?;
+                    if panel.is_none() {
+                        _mouse_capture_guard = None;
+                    }
+                    continue;
+                }
+                if let Event::Mouse(mouse_event) = event {
+                    if let Some(result) =
+                        self.handle_mouse(mouse_event, &mut panel, &mut prompt_guard)?
+                    {
+                        return Ok(result);
+                    }
+                    continue;
+                }
+                let Event::Key(KeyEvent {
                     code, modifiers, ..
-                }) = read()?
-            {
-                if modifiers == crossterm::event::KeyModifiers::CONTROL {
-                    if code == KeyCode::Char('c') {
+                }) = event
+                else {
+                    continue;
+                };
+                let is_key = |key: &KeyEvent| code == key.code && modifiers == key.modifiers;
+
+                if let Some(form) = self.form.as_mut() {
+                    if is_key(&cancel_key)
+                        || (modifiers == KeyModifiers::CONTROL && code == KeyCode::Char('c'))
+                    {
                         return Ok(SessionResult::Cancelled);
                     }
+                    if is_key(&back_key) {
+                        // Going back from an empty form returns to the panel.
+                        if form.is_empty() {
+                            self.form = None;
+                        } else {
+                            form.pop();
+                        }
+                    } else if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                        continue;
+                    } else if self.handle_form_key(code)? {
+                        return Ok(SessionResult::Saved);
+                    }
+                    if let Some(ref mut p) = panel {
+                        self.try_draw_panel(p, &mut prompt_guard)?;
+                    }
                     continue;
                 }
-                match code {
-                    KeyCode::Char(c) => {
-                        self.sequence.push(c);
-                        if let Some(mapping) = self.mappings.match_sequence(&self.sequence) {
-                            return Ok(SessionResult::Command(mapping.format_command()));
-                        }
 
-                        if !self.mappings.has_partial_match(&self.sequence) {
-                            return Ok(SessionResult::NoMatch);
+                if code == save_key.code && modifiers == save_key.modifiers {
+                    let input = std::env::var("LEADR_CURRENT_INPUT").unwrap_or_default();
+                    if input.trim().is_empty() {
+                        continue;
+                    }
+                    // The form needs the panel, so show it right away.
+                    if panel.is_none() {
+                        panel = self.try_new_panel(&mut prompt_guard)?;
+                        _mouse_capture_guard = self.capture_mouse(&panel);
+                    }
+                    let Some(ref mut p) = panel else {
+                        // Without a panel there is nowhere to show the form.
+                        if self.config.panel.fail_silently {
+                            continue;
                         }
+                        return Err(eyre!(
+                            "Can't save the command line as a mapping: the panel couldn't be shown."
+                        ));
+                    };
+                    self.form = Some(SaveForm::new(input));
+                    self.try_draw_panel(p, &mut prompt_guard)?;
+                    continue;
+                }
+
+                let forward = if is_key(&next_page_key) || code == KeyCode::PageDown {
+                    Some(true)
+                } else if is_key(&previous_page_key) || code == KeyCode::PageUp {
+                    Some(false)
+                } else {
+                    None
+                };
+                if let Some(forward) = forward {
+                    if let Some(ref mut p) = panel {
+                        p.flip_page(forward);
+                        self.try_draw_panel(p, &mut prompt_guard)?;
                     }
-                    KeyCode::Backspace => {
-                        self.sequence.pop();
+                    continue;
+                }
+
+                let direction = match (code, modifiers) {
+                    (KeyCode::Up, _) => Some(Direction::Up),
+                    (KeyCode::Down, _) => Some(Direction::Down),
+                    (KeyCode::Left, _) => Some(Direction::Left),
+                    (KeyCode::Right, _) => Some(Direction::Right),
+                    (KeyCode::Char('k'), KeyModifiers::CONTROL) => Some(Direction::Up),
+                    (KeyCode::Char('j'), KeyModifiers::CONTROL) => Some(Direction::Down),
+                    _ => None,
+                };
+                if let Some(direction) = direction {
+                    if let Some(ref mut p) = panel {
+                        p.move_highlight(direction);
+                        self.try_draw_panel(p, &mut prompt_guard)?;
                     }
-                    KeyCode::Esc => {
+                    continue;
+                }
+
+                if is_key(&cancel_key) {
+                    return Ok(SessionResult::Cancelled);
+                }
+                if is_key(&back_key) {
+                    self.sequence.pop();
+                    if let Some(ref mut p) = panel {
+                        self.try_draw_panel(p, &mut prompt_guard)?;
+                    }
+                    continue;
+                }
+
+                if modifiers == KeyModifiers::CONTROL {
+                    if code == KeyCode::Char('c') {
                         return Ok(SessionResult::Cancelled);
                     }
+                    continue;
+                }
+                match code {
+                    KeyCode::Char(c) => {
+                        if let Some(result) = self.push_key(&c.to_string()) {
+                            return Ok(result);
+                        }
+                    }
+                    KeyCode::Enter => {
+                        let highlighted = panel
+                            .as_ref()
+                            .and_then(Panel::highlighted_key)
+                            .map(String::from);
+                        if let Some(key) = highlighted
+                            && let Some(result) = self.push_key(&key)
+                        {
+                            return Ok(result);
+                        }
+                    }
                     _ => {}
                 }
 
                 if let Some(ref mut p) = panel {
-                    self.try_draw_panel(p)?;
+                    self.try_draw_panel(p, &mut prompt_guard)?;
                 }
             }
         }
     }
 
+    /// Appends `key` to the typed sequence, as if it had been typed.
+    /// Returns the result of the session if the sequence is complete or can't match anymore.
+    fn push_key(&mut self, key: &str) -> Option<SessionResult> {
+        self.sequence.push_str(key);
+        if let Some(mapping) = self.mappings.match_sequence(&self.sequence) {
+            return Some(SessionResult::Command(mapping.format_command()));
+        }
+
+        if !self.mappings.has_partial_match(&self.sequence) {
+            return Some(SessionResult::NoMatch);
+        }
+        None
+    }
+
+    /// Redraws the prompt line and panel after the terminal was resized.
+    /// The panel is removed where it was drawn before, and placed anew around the prompt line.
+    fn handle_resize(
+        &self,
+        panel: &mut Option<Panel>,
+        prompt_guard: &mut Option<prompt::PromptGuard>,
+    ) -> Result<()> {
+        if let Some(p) = panel.as_mut() {
+            match p.relocate() {
+                Ok(()) => {}
+                // Dropping the panel makes the session create a new one.
+                Err(_) if self.config.panel.fail_silently => *panel = None,
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(guard) = prompt_guard.as_mut()
+            && guard.relocate().is_err()
+        {
+            *prompt_guard = None;
+        }
+
+        self.redraw_prompt_line(prompt_guard)?;
+        if let Some(p) = panel.as_mut() {
+            self.try_draw_panel(p, prompt_guard)?;
+        }
+        Ok(())
+    }
+
+    /// Handles a mouse event on the panel: clicking an entry selects it, clicking outside
+    /// the panel cancels and scrolling flips pages.
+    /// Returns the result of the session if the event ends it.
+    fn handle_mouse(
+        &mut self,
+        event: MouseEvent,
+        panel: &mut Option<Panel>,
+        prompt_guard: &mut Option<prompt::PromptGuard>,
+    ) -> Result<Option<SessionResult>> {
+        let Some(p) = panel.as_mut() else {
+            return Ok(None);
+        };
+        if self.form.is_some() {
+            return Ok(None);
+        }
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(key) = p.key_at(event.column, event.row).map(String::from) {
+                    if let Some(result) = self.push_key(&key) {
+                        return Ok(Some(result));
+                    }
+                } else if !p.contains(event.column, event.row) {
+                    return Ok(Some(SessionResult::Cancelled));
+                } else {
+                    return Ok(None);
+                }
+            }
+            MouseEventKind::ScrollDown => p.flip_page(true),
+            MouseEventKind::ScrollUp => p.flip_page(false),
+            _ => return Ok(None),
+        }
+        self.try_draw_panel(p, prompt_guard)?;
+        Ok(None)
+    }
+
+    /// Handles a key press while the save form is open.
+    /// Returns true once the new mapping has been saved.
+    fn handle_form_key(&mut self, code: KeyCode) -> Result<bool> {
+        let Some(form) = self.form.as_mut() else {
+            return Ok(false);
+        };
+
+        match code {
+            KeyCode::Char(c) => form.push(c),
+            KeyCode::Backspace => form.pop(),
+            KeyCode::Enter | KeyCode::Tab => {
+                let complete = form.submit(&self.mappings);
+                if complete {
+                    let mapping = Mapping {
+                        command: form.command.clone(),
+                        description: Some(form.description.clone())
+                            .filter(|description| !description.is_empty()),
+                        ..Default::default()
+                    };
+                    let result =
+                        mapping_file::resolve_path(&self.config_dir, Some(&self.config.save_file))
+                            .and_then(|path| {
+                                mapping_file::add_mapping(
+                                    &self.mappings,
+                                    &form.sequence,
+                                    mapping,
+                                    &path,
+                                )
+                            });
+                    match result {
+                        Ok(()) => return Ok(true),
+                        Err(e) => form.error = Some(e.to_string()),
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(false)
+    }
+
+    /// Redraws the prompt line if enabled and possible.
+    fn redraw_prompt_line(&self, prompt_guard: &mut Option<prompt::PromptGuard>) -> Result<()> {
+        if let Some(guard) = prompt_guard
+            && self.config.redraw_prompt_line
+        {
+            guard.redraw()?;
+        }
+        Ok(())
+    }
+
     /// Try creating a new panel and draw upon success.
     /// Will return Ok(None) if panel creation fails but fail_silently is set.
-    fn try_new_panel(&self) -> Result<Option<Panel>> {
-        match Panel::try_new(self.config.panel.clone(), self.theme.clone()) {
-            Ok(mut p) => {
-                self.try_draw_panel(&mut p)?;
+    fn try_new_panel(
+        &self,
+        prompt_guard: &mut Option<prompt::PromptGuard>,
+    ) -> Result<Option<Panel>> {
+        // Without a prompt line to redraw, the alternate screen would hide the prompt.
+        let alternate_screen = prompt_guard.is_some() && self.config.redraw_prompt_line;
+        let (cancel_key, back_key) = self.config.cancel_and_back_key_events()?;
+        match Panel::try_new(
+            self.config.panel.clone(),
+            self.theme.clone(),
+            alternate_screen,
+        ) {
+            Ok(p) => {
+                let mut p = p.with_keys(cancel_key, back_key);
+                self.try_draw_panel(&mut p, prompt_guard)?;
                 Ok(Some(p))
             }
             Err(_) if self.config.panel.fail_silently => Ok(None),
@@ -101,9 +372,31 @@ impl LeadrSession {
         }
     }
 
+    /// Captures the mouse for a newly shown panel if enabled. This is only tried once per
+    /// panel: without capture, clicks are simply left to the terminal.
+    fn capture_mouse(&self, panel: &Option<Panel>) -> Option<MouseCaptureGuard> {
+        if self.config.panel.mouse && panel.is_some() {
+            MouseCaptureGuard::new().ok()
+        } else {
+            None
+        }
+    }
+
     /// Try drawing the panel, respecting the fail_silently setting.
-    fn try_draw_panel(&self, panel: &mut Panel) -> Result<()> {
-        match panel.draw(&self.sequence, &self.mappings) {
+    /// Redraws the prompt line if the panel moved to the alternate screen.
+    fn try_draw_panel(
+        &self,
+        panel: &mut Panel,
+        prompt_guard: &mut Option<prompt::PromptGuard>,
+    ) -> Result<()> {
+        let result = match &self.form {
+            Some(form) => panel.draw_form(form, &self.mappings),
+            None => panel.draw(&self.sequence, &self.mappings),
+        };
+        if panel.take_prompt_redraw() {
+            self.redraw_prompt_line(prompt_guard)?;
+        }
+        match result {
             Ok(()) => Ok(()),
             Err(_) if self.config.panel.fail_silently => Ok(()),
             Err(e) => Err(e),
@@ -0,0 +1,434 @@
+//! Suggest new mappings based on the shell history.
+//!
+//! Commands (and command prefixes like `git commit -m`) that are typed over and over again are
+//! good candidates for mappings. This module reads the history files of the supported shells,
+//! counts repeated commands that aren't covered by an existing mapping yet and proposes
+//! mnemonic key sequences for them.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use directories::BaseDirs;
+
+use crate::{Mapping, Mappings, mappings::RESERVED_KEYS};
+
+/// Minimum number of times a command has to appear in the history to be suggested.
+const MIN_COUNT: usize = 3;
+
+/// Commands shorter than this are quick enough to type by hand.
+const MIN_COMMAND_LENGTH: usize = 6;
+
+/// Maximum length of a generated key sequence.
+const MAX_SEQUENCE_LENGTH: usize = 4;
+
+/// Maximum number of suggestions to return.
+const MAX_SUGGESTIONS: usize = 15;
+
+/// A proposed mapping derived from the shell history.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub sequence: String,
+    pub command: String,
+    /// How often the command (or prefix) appears in the history.
+    pub count: usize,
+    /// Whether this is the beginning of a command rather than a complete one.
+    pub is_prefix: bool,
+}
+
+impl Suggestion {
+    /// Estimated number of keystrokes saved over the whole history,
+    /// counting the leadr key as a single keystroke.
+    pub fn saved_keystrokes(&self) -> usize {
+        let typed = self.command.chars().count();
+        let with_leadr = self.sequence.chars().count() + 1;
+        self.count * typed.saturating_sub(with_leadr)
+    }
+
+    pub fn to_mapping(&self) -> Mapping {
+        Mapping {
+            command: self.command.clone(),
+            description: Some(if self.is_prefix {
+                format!("Start `{}`", self.command.trim_end())
+            } else {
+                format!("Run `{}`", self.command)
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the history files of all supported shells that exist on this machine.
+pub fn history_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(histfile) = std::env::var("HISTFILE") {
+        files.push(PathBuf::from(histfile));
+    }
+    if let Some(dirs) = BaseDirs::new() {
+        let home = dirs.home_dir();
+        let zdotdir = std::env::var("ZDOTDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| home.to_path_buf());
+        let data_dir = std::env::var("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| home.join(".local/share"));
+
+        files.push(home.join(".bash_history"));
+        files.push(zdotdir.join(".zsh_history"));
+        files.push(zdotdir.join(".zhistory"));
+        files.push(data_dir.join("fish/fish_history"));
+    }
+
+    let mut existing: Vec<PathBuf> = Vec::new();
+    for file in files {
+        if file.is_file() && !existing.contains(&file) {
+            existing.push(file);
+        }
+    }
+    existing
+}
+
+/// Reads all commands from a history file, detecting the file format from its contents.
+pub fn read_history(path: &Path) -> Result<Vec<String>> {
+    let bytes = fs::read(path)?;
+    let contents = String::from_utf8_lossy(&bytes);
+
+    let is_fish = contents.lines().any(|line| line.starts_with("- cmd: "));
+    let is_zsh = contents.lines().any(|line| line.starts_with(": "));
+    Ok(if is_fish {
+        parse_fish_history(&contents)
+    } else if is_zsh {
+        parse_zsh_history(&contents)
+    } else {
+        parse_bash_history(&contents)
+    })
+}
+
+/// Parses a bash history file, skipping `HISTTIMEFORMAT` timestamp lines.
+fn parse_bash_history(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter(|line| !(line.starts_with('#') && line[1..].chars().all(|c| c.is_ascii_digit())))
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Parses a zsh history file, both in the plain and the extended (`: <time>:<duration>;cmd`)
+/// format. Multi-line commands are joined.
+fn parse_zsh_history(contents: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut current = String::new();
+
+    for line in contents.lines() {
+        let line = if current.is_empty() && line.starts_with(": ") {
+            line.split_once(';').map(|(_, cmd)| cmd).unwrap_or(line)
+        } else {
+            line
+        };
+
+        if let Some(continued) = line.strip_suffix('\\') {
+            current.push_str(continued);
+            current.push('\n');
+            continue;
+        }
+
+        current.push_str(line);
+        let command = current.trim().to_string();
+        if !command.is_empty() {
+            commands.push(command);
+        }
+        current.clear();
+    }
+
+    commands
+}
+
+/// Parses the YAML-like fish history format.
+fn parse_fish_history(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(|cmd| cmd.replace("\\n", "\n").replace("\\\\", "\\"))
+        .map(|cmd| cmd.trim().to_string())
+        .filter(|cmd| !cmd.is_empty())
+        .collect()
+}
+
+/// Finds frequently used commands and command prefixes not covered by any mapping yet
+/// and assigns them non-conflicting key sequences.
+pub fn suggest(history: &[String], mappings: &Mappings) -> Vec<Suggestion> {
+    let mut commands: HashMap<&str, usize> = HashMap::new();
+    let mut prefixes: HashMap<String, usize> = HashMap::new();
+
+    for command in history {
+        if command.contains('\n') {
+            continue;
+        }
+        *commands.entry(command.as_str()).or_default() += 1;
+
+        // Every leading group of words (except the full command) is a potential prefix.
+        let words: Vec<&str> = command.split_whitespace().collect();
+        for length in 2..words.len() {
+            *prefixes
+                .entry(format!("{} ", words[..length].join(" ")))
+                .or_default() += 1;
+        }
+    }
+
+    // Mappings that are unavailable here don't cover a command, though their sequences are
+    // still taken below.
+    let covered: Vec<String> = mappings
+        .iter()
+        .map(|(_, mapping)| mapping.command.replace("#CURSOR", "").trim().to_string())
+        .filter(|command| !command.is_empty())
+        .collect();
+    let is_covered = |command: &str| {
+        let command = command.trim_end();
+        covered
+            .iter()
+            .any(|c| command == c || command.starts_with(&format!("{c} ")))
+    };
+
+    let mut candidates: Vec<(String, usize, bool)> = commands
+        .into_iter()
+        .map(|(command, count)| (command.to_string(), count, false))
+        .chain(
+            prefixes
+                .into_iter()
+                .map(|(prefix, count)| (prefix, count, true)),
+        )
+        .filter(|(command, count, _)| {
+            *count >= MIN_COUNT
+                && command.trim_end().chars().count() >= MIN_COMMAND_LENGTH
+                && !is_covered(command)
+        })
+        .collect();
+
+    // A prefix that is only ever used as part of a longer candidate adds nothing.
+    let all = candidates.clone();
+    candidates.retain(|(command, count, is_prefix)| {
+        !is_prefix
+            || !all.iter().any(|(other, other_count, _)| {
+                other != command && other.starts_with(command.as_str()) && other_count >= count
+            })
+    });
+
+    // Longer and more frequent commands are worth more, so they get the first pick of sequences.
+    candidates.sort_by(|a, b| {
+        let score = |(command, count, _): &(String, usize, bool)| command.chars().count() * count;
+        score(b).cmp(&score(a)).then_with(|| a.0.cmp(&b.0))
+    });
+
+    let mut taken = mappings.clone();
+    let mut suggestions = Vec::new();
+    for (command, count, is_prefix) in candidates {
+        if suggestions.len() >= MAX_SUGGESTIONS {
+            break;
+        }
+        let Some(sequence) = mnemonic_sequences(&command).into_iter().find(|sequence| {
+            !RESERVED_KEYS.contains(&sequence.as_str()) && taken.find_conflict(sequence).is_none()
+        }) else {
+            continue;
+        };
+
+        let suggestion = Suggestion {
+            sequence,
+            command,
+            count,
+            is_prefix,
+        };
+        if suggestion.saved_keystrokes() == 0 {
+            continue;
+        }
+        taken.insert(suggestion.sequence.clone(), suggestion.to_mapping());
+        suggestions.push(suggestion);
+    }
+
+    suggestions.sort_by_key(|s| std::cmp::Reverse(s.saved_keystrokes()));
+    suggestions
+}
+
+pub fn render_table(suggestions: &[Suggestion]) -> String {
+    let mut table = format!(
+        "{:<8} {:<5} {:<7} {}\n{:-<8} {:-<5} {:-<7} {:-<40}\n",
+        "Sequence", "Uses", "Saved", "Command", "", "", "", ""
+    );
+    for suggestion in suggestions {
+        table.push_str(&format!(
+            "{:<8} {:<5} {:<7} {}\n",
+            suggestion.sequence,
+            suggestion.count,
+            suggestion.saved_keystrokes(),
+            suggestion.command
+        ));
+    }
+    table
+}
+
+/// Generates candidate sequences for a command, most mnemonic first.
+///
+/// The first choice are the initials of the words, e.g. `git status` becomes `gs`.
+/// Alternatives extend the initials with further letters of the last word, drop the first word
+/// (which is often shared by many commands, like `git`) and finally append digits.
+fn mnemonic_sequences(command: &str) -> Vec<String> {
+    let words: Vec<Vec<char>> = command
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .map(|c| c.to_ascii_lowercase())
+                .collect::<Vec<char>>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let words = &words[..words.len().min(MAX_SEQUENCE_LENGTH)];
+    let initials: String = words.iter().map(|word| word[0]).collect();
+
+    let mut sequences = extend_with_last_word(&initials, words);
+    if words.len() > 1 {
+        let rest: String = words[1..].iter().map(|word| word[0]).collect();
+        sequences.extend(extend_with_last_word(&rest, &words[1..]));
+
+        let first_and_last = format!("{}{}", words[0][0], words[words.len() - 1][0]);
+        sequences.push(first_and_last);
+    }
+
+    let base: String = initials.chars().take(MAX_SEQUENCE_LENGTH - 1).collect();
+    for digit in 1..=9 {
+        sequences.push(format!("{base}{digit}"));
+    }
+
+    let mut unique = Vec::new();
+    for sequence in sequences {
+        if !unique.contains(&sequence) {
+            unique.push(sequence);
+        }
+    }
+    unique
+}
+
+/// Returns `initials` followed by versions of it extended with further letters of the last word.
+fn extend_with_last_word(initials: &str, words: &[Vec<char>]) -> Vec<String> {
+    let mut sequences = vec![initials.to_string()];
+    let mut extended = initials.to_string();
+    for c in words[words.len() - 1].iter().skip(1) {
+        if extended.len() >= MAX_SEQUENCE_LENGTH {
+            break;
+        }
+        extended.push(*c);
+        sequences.push(extended.clone());
+    }
+    sequences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UnknownKeys, test_util::TempDir};
+
+    #[test]
+    fn test_parse_bash_history() {
+        let contents = "#1700000000\ngit status\n\nls -la\n#not a timestamp\n";
+        assert_eq!(
+            parse_bash_history(contents),
+            vec!["git status", "ls -la", "#not a timestamp"]
+        );
+    }
+
+    #[test]
+    fn test_parse_zsh_history() {
+        let contents = ": 1700000000:0;git status\n: 1700000001:0;echo a\\\nb\nls\n";
+        assert_eq!(
+            parse_zsh_history(contents),
+            vec!["git status", "echo a\nb", "ls"]
+        );
+    }
+
+    #[test]
+    fn test_parse_fish_history() {
+        let contents = "- cmd: git status\n  when: 1700000000\n- cmd: echo a\\nb\n  when: 1\n";
+        assert_eq!(
+            parse_fish_history(contents),
+            vec!["git status", "echo a\nb"]
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_sequences() {
+        let sequences = mnemonic_sequences("docker compose up -d");
+        assert_eq!(sequences[0], "dcud");
+        assert!(sequences.contains(&"dcu1".to_string()));
+
+        let sequences = mnemonic_sequences("kubectl get pods");
+        assert_eq!(&sequences[..6], &["kgp", "kgpo", "gp", "gpo", "gpod", "kp"]);
+    }
+
+    #[test]
+    fn test_suggest_skips_covered_and_rare_commands() {
+        let history: Vec<String> = [
+            "git status",
+            "git status",
+            "git status",
+            "cargo build --release",
+            "cargo build --release",
+            "cargo build --release",
+            "make install",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let suggestions = suggest(&history, &Mappings::default());
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "cargo build --release");
+        assert_eq!(suggestions[0].sequence, "cbr");
+        assert_eq!(suggestions[0].count, 3);
+        assert_eq!(suggestions[0].saved_keystrokes(), 3 * (21 - 4));
+    }
+
+    #[test]
+    fn test_suggest_prefixes_without_conflicts() {
+        let history: Vec<String> = (0..5)
+            .map(|i| format!("git commit -m message{i}"))
+            .chain((0..4).map(|i| format!("git checkout branch{i}")))
+            .collect();
+
+        let suggestions = suggest(&history, &Mappings::default());
+        let commands: Vec<&str> = suggestions.iter().map(|s| s.command.as_str()).collect();
+
+        // "git commit " is only ever used as part of "git commit -m "
+        assert_eq!(commands, vec!["git commit -m ", "git checkout "]);
+        assert!(suggestions.iter().all(|s| s.is_prefix));
+
+        // Everything starting with "gc" conflicts with the default "gc" mapping
+        assert_eq!(suggestions[0].sequence, "cm");
+        assert_eq!(suggestions[1].sequence, "ch");
+    }
+
+    #[test]
+    fn test_suggest_commands_only_mapped_where_unavailable() {
+        let dir = TempDir::with_files(
+            "suggest",
+            &[(
+                "mappings.toml",
+                "[cbr]\ncommand = \"cargo build --release\"\nrequires = [\"leadr-nope\"]\n",
+            )],
+        );
+        let mappings = Mappings::load(&[dir.path().to_path_buf()], UnknownKeys::Error).unwrap();
+        let history = vec!["cargo build --release".to_string(); 3];
+
+        let suggestions = suggest(&history, &mappings);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].command, "cargo build --release");
+        // The unavailable mapping still takes its sequence on other hosts.
+        assert_ne!(suggestions[0].sequence, "cbr");
+    }
+}
@@ -0,0 +1,40 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A scratch directory for tests, removed again when dropped so that failing assertions
+/// don't leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates a fresh directory named after `name` containing the given files, as pairs of
+    /// relative path and contents.
+    pub fn with_files(name: &str, files: &[(&str, &str)]) -> Self {
+        let path = std::env::temp_dir().join(format!("leadr-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let dir = Self(path);
+        for (file, contents) in files {
+            dir.write(file, contents);
+        }
+        dir
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to the file at the relative `path`, creating its parents as needed.
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
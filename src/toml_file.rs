@@ -0,0 +1,254 @@
+//! Parsing of TOML configuration files with precise error reporting.
+//!
+//! Errors and unknown keys are reported with the file path, line, column and a snippet of the
+//! offending text, so a typo like `exectue = true` doesn't silently do nothing.
+
+use std::{ops::Range, path::Path};
+
+use color_eyre::eyre::{Result, eyre};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use toml_edit::{ImDocument, Item};
+
+/// How to treat keys that leadr doesn't know.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownKeys {
+    /// Silently ignore unknown keys.
+    Ignore,
+    /// Print a warning for every unknown key, except while the panel is shown.
+    #[default]
+    Warn,
+    /// Refuse to load files containing unknown keys.
+    Error,
+}
+
+impl UnknownKeys {
+    /// How unknown keys are treated while the panel is shown. Warnings printed then would end
+    /// up over the prompt and the panel, so they are left to commands like `--list`.
+    pub fn in_session(self) -> Self {
+        match self {
+            UnknownKeys::Warn => UnknownKeys::Ignore,
+            other => other,
+        }
+    }
+}
+
+/// Parses the contents of a TOML file, reporting unknown keys according to `unknown_keys`.
+pub fn parse<T: DeserializeOwned>(
+    path: &Path,
+    contents: &str,
+    unknown_keys: UnknownKeys,
+) -> Result<T> {
+    let (value, unknown) = parse_collecting_unknown(path, contents)?;
+    report_unknown_keys(&unknown, unknown_keys)?;
+    Ok(value)
+}
+
+/// Parses the contents of a TOML file, returning the descriptions of all unknown keys
+/// alongside the value instead of reporting them.
+pub fn parse_collecting_unknown<T: DeserializeOwned>(
+    path: &Path,
+    contents: &str,
+) -> Result<(T, Vec<String>)> {
+    let mut unknown_paths = Vec::new();
+    let deserializer = toml::Deserializer::new(contents);
+    let value = serde_ignored::deserialize(deserializer, |path| {
+        unknown_paths.push(path_segments(&path))
+    })
+    .map_err(|e| eyre!(describe(path, contents, e.span(), e.message())))?;
+
+    let unknown = unknown_paths
+        .iter()
+        .map(|segments| describe_unknown_key(path, contents, segments))
+        .collect();
+
+    Ok((value, unknown))
+}
+
+/// Deserializes an already parsed TOML value, returning the paths of all unknown keys
+/// alongside the value.
+pub fn from_value_collecting_unknown<T: DeserializeOwned>(
+    value: toml::Value,
+) -> Result<(T, Vec<Vec<String>>)> {
+    let mut unknown_paths = Vec::new();
+    let value = serde_ignored::deserialize(value, |path| unknown_paths.push(path_segments(&path)))?;
+    Ok((value, unknown_paths))
+}
+
+/// Describes an unknown key given by its path segments, including its location in the file.
+pub fn describe_unknown_key(path: &Path, contents: &str, segments: &[String]) -> String {
+    let span = ImDocument::parse(contents)
+        .ok()
+        .and_then(|document| key_span(document.as_item(), segments));
+    let message = format!("unknown key '{}'", segments.join("."));
+    describe(path, contents, span, &message)
+}
+
+/// Prints or returns the given unknown key descriptions, depending on `unknown_keys`.
+pub fn report_unknown_keys(unknown: &[String], unknown_keys: UnknownKeys) -> Result<()> {
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    match unknown_keys {
+        UnknownKeys::Ignore => Ok(()),
+        UnknownKeys::Warn => {
+            for description in unknown {
+                eprintln!("Warning: {description}");
+            }
+            Ok(())
+        }
+        UnknownKeys::Error => Err(eyre!(unknown.join("\n"))),
+    }
+}
+
+/// Converts a serde_ignored path into its key segments.
+fn path_segments(path: &serde_ignored::Path) -> Vec<String> {
+    match path {
+        serde_ignored::Path::Root => Vec::new(),
+        serde_ignored::Path::Seq { parent, index } => {
+            let mut segments = path_segments(parent);
+            segments.push(index.to_string());
+            segments
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            let mut segments = path_segments(parent);
+            segments.push(key.clone());
+            segments
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => path_segments(parent),
+    }
+}
+
+/// Finds the location of the last key in `segments` within the document.
+fn key_span(item: &Item, segments: &[String]) -> Option<Range<usize>> {
+    let (first, rest) = segments.split_first()?;
+
+    if let Ok(index) = first.parse::<usize>()
+        && let Some(array) = item.as_array()
+    {
+        let value = array.get(index)?;
+        return if rest.is_empty() {
+            value.span()
+        } else {
+            key_span(&Item::Value(value.clone()), rest)
+        };
+    }
+
+    let (key, child) = item.as_table_like()?.get_key_value(first)?;
+    if rest.is_empty() {
+        key.span()
+    } else {
+        key_span(child, rest)
+    }
+}
+
+/// Formats a message with the file path, line, column and a snippet of the offending text.
+fn describe(path: &Path, contents: &str, span: Option<Range<usize>>, message: &str) -> String {
+    let Some(span) = span else {
+        return format!("{}: {message}", path.display());
+    };
+
+    let start = span.start.min(contents.len());
+    let line_start = contents[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = contents[start..]
+        .find('\n')
+        .map_or(contents.len(), |i| start + i);
+    let line_number = contents[..start].matches('\n').count() + 1;
+    let column = contents[line_start..start].chars().count() + 1;
+
+    let line = &contents[line_start..line_end];
+    let underline_length = contents[start..span.end.clamp(start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    format!(
+        "{path}:{line_number}:{column}: {message}\n\
+         {gutter} |\n\
+         {line_number} | {line}\n\
+         {gutter} | {padding}{underline}",
+        path = path.display(),
+        padding = " ".repeat(column - 1),
+        underline = "^".repeat(underline_length),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Entry {
+        #[allow(dead_code)]
+        command: String,
+    }
+
+    #[test]
+    fn test_unknown_key_location() {
+        let contents = "[gs]\ncommand = \"git status\"\nexectue = true\n";
+        let (_, unknown): (HashMap<String, Entry>, _) =
+            parse_collecting_unknown(Path::new("mappings.toml"), contents).unwrap();
+
+        assert_eq!(
+            unknown,
+            vec![
+                "mappings.toml:3:1: unknown key 'gs.exectue'\n  |\n3 | exectue = true\n  | ^^^^^^^"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_key_policy() {
+        let contents = "[gs]\ncommand = \"git status\"\nexectue = true\n";
+        let path = Path::new("mappings.toml");
+
+        assert!(parse::<HashMap<String, Entry>>(path, contents, UnknownKeys::Ignore).is_ok());
+        assert!(parse::<HashMap<String, Entry>>(path, contents, UnknownKeys::Warn).is_ok());
+        let err = parse::<HashMap<String, Entry>>(path, contents, UnknownKeys::Error).unwrap_err();
+        assert!(err.to_string().contains("unknown key 'gs.exectue'"));
+    }
+
+    #[test]
+    fn test_error_location() {
+        let contents = "[gs]\ncommand = 42\n";
+        let err = parse::<HashMap<String, Entry>>(
+            Path::new("mappings.toml"),
+            contents,
+            UnknownKeys::Error,
+        )
+        .unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.starts_with("mappings.toml:2:11: "), "{message}");
+        assert!(
+            message.contains("2 | command = 42\n  |           ^^"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let contents = "[gs\ncommand = \"git status\"\n";
+        let err = parse::<HashMap<String, Entry>>(
+            Path::new("mappings.toml"),
+            contents,
+            UnknownKeys::Error,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().starts_with("mappings.toml:1:4: "), "{err}");
+    }
+
+    #[test]
+    fn test_no_warnings_in_session() {
+        assert_eq!(UnknownKeys::Warn.in_session(), UnknownKeys::Ignore);
+        assert_eq!(UnknownKeys::Error.in_session(), UnknownKeys::Error);
+        assert!(report_unknown_keys(&["typo".into()], UnknownKeys::Warn.in_session()).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Area {
     pub x: u16,
     pub y: u16,
@@ -8,10 +9,14 @@ pub struct Area {
     pub height: u16,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct ColumnLayout {
+    /// Width of each column in characters.
     pub width: u16,
+    /// Space between two columns in characters.
     pub spacing: u16,
+    /// Whether to center the columns within the panel.
     pub centred: bool,
 }
 
@@ -26,6 +31,12 @@ impl std::default::Default for ColumnLayout {
 }
 
 impl Area {
+    /// Returns whether the cell at `x` and `y` lies within the area.
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        (self.x..self.x.saturating_add(self.width)).contains(&x)
+            && (self.y..self.y.saturating_add(self.height)).contains(&y)
+    }
+
     pub fn split_horizontally(
         &self,
         column_layout: &ColumnLayout,
@@ -70,6 +81,21 @@ impl Area {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_contains() {
+        let area = Area {
+            x: 2,
+            y: 3,
+            width: 4,
+            height: 2,
+        };
+        assert!(area.contains(2, 3));
+        assert!(area.contains(5, 4));
+        assert!(!area.contains(6, 4));
+        assert!(!area.contains(5, 5));
+        assert!(!area.contains(1, 3));
+    }
+
     #[test]
     fn test_split_no_centering() {
         let area = Area {
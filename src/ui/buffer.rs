@@ -0,0 +1,291 @@
+//! An in-memory grid of terminal cells the panel is drawn into. Comparing it to the previously
+//! drawn frame lets only the changed cells be written, which avoids flicker on slow connections.
+
+use std::{fmt::Display, io::Write};
+
+use crossterm::{
+    QueueableCommand, cursor,
+    style::{ContentStyle, PrintStyledContent, StyledContent},
+    terminal,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::ui::{area::Area, text};
+
+/// A single terminal cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// The grapheme shown in the cell, e.g. a character followed by combining characters.
+    /// Empty for the second cell of a wide character.
+    pub symbol: String,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".into(),
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+/// Cells covering an area of the screen, addressed by screen coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buffer {
+    area: Area,
+    cells: Vec<Cell>,
+}
+
+impl Buffer {
+    /// Returns a buffer of blank cells covering `area`.
+    pub fn empty(area: Area) -> Self {
+        Self {
+            area,
+            cells: vec![Cell::default(); area.width as usize * area.height as usize],
+        }
+    }
+
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        self.area.contains(x, y).then(|| {
+            (y - self.area.y) as usize * self.area.width as usize + (x - self.area.x) as usize
+        })
+    }
+
+    /// Returns the cell at the given screen coordinates, if the buffer covers it.
+    pub fn cell(&self, x: u16, y: u16) -> Option<&Cell> {
+        self.index(x, y).map(|index| &self.cells[index])
+    }
+
+    /// Writes `text` starting at the given cell and returns the column after it.
+    /// Whatever doesn't fit into the buffer is cut off.
+    pub fn set_string(&mut self, x: u16, y: u16, text: &str, style: ContentStyle) -> u16 {
+        let mut x = x;
+        let mut last: Option<usize> = None;
+        for grapheme in text.graphemes(true) {
+            let width = text::width(grapheme).min(2) as u16;
+            if width == 0 {
+                // Lone combining characters belong to the cell written last.
+                if let Some(index) = last {
+                    self.cells[index].symbol.push_str(grapheme);
+                }
+                continue;
+            }
+            let (Some(index), Some(_)) = (self.index(x, y), self.index(x + width - 1, y)) else {
+                break;
+            };
+            self.clear_wide_character(x, y);
+            self.cells[index] = Cell {
+                symbol: grapheme.to_string(),
+                style,
+            };
+            if width == 2 {
+                self.clear_wide_character(x + 1, y);
+                self.cells[index + 1] = Cell {
+                    symbol: String::new(),
+                    style,
+                };
+            }
+            last = Some(index);
+            x += width;
+        }
+        x
+    }
+
+    /// Like [`Buffer::set_string`], for content styled by the theme.
+    pub fn set_styled<D: Display>(&mut self, x: u16, y: u16, content: &StyledContent<D>) -> u16 {
+        self.set_string(x, y, &content.content().to_string(), *content.style())
+    }
+
+    /// Blanks out the other half of a wide character before the cell at `x` is overwritten.
+    fn clear_wide_character(&mut self, x: u16, y: u16) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+        let other = if self.cells[index].symbol.is_empty() {
+            x.checked_sub(1)
+        } else if text::width(&self.cells[index].symbol) == 2 {
+            Some(x + 1)
+        } else {
+            None
+        };
+        if let Some(other) = other.and_then(|other| self.index(other, y)) {
+            self.cells[other].symbol = " ".into();
+        }
+    }
+
+    /// Writes the cells that differ from `previous`, or all of them if there is no previous
+    /// frame of the same area. Runs of cells with the same style are written at once and
+    /// the whole update is shown at once by terminals supporting synchronized output.
+    pub fn draw(&self, tty: &mut impl Write, previous: Option<&Buffer>) -> std::io::Result<()> {
+        let previous = previous.filter(|previous| previous.area == self.area);
+        tty.queue(terminal::BeginSynchronizedUpdate)?
+            .queue(cursor::SavePosition)?;
+
+        // Where the terminal's cursor is after the last run, to save moving it there.
+        let mut position = None;
+        // The run of changed cells being collected, with its start, end and style.
+        let mut run: Option<((u16, u16), u16, ContentStyle, String)> = None;
+        for y in self.area.y..self.area.y + self.area.height {
+            for x in self.area.x..self.area.x + self.area.width {
+                let cell = &self.cells[self.index(x, y).unwrap()];
+                let changed = previous.is_none_or(|previous| previous.cell(x, y) != Some(cell));
+                if !changed || cell.symbol.is_empty() {
+                    continue;
+                }
+                let width = text::width(&cell.symbol) as u16;
+                match &mut run {
+                    Some(((_, run_y), end, style, symbols))
+                        if *run_y == y && *end == x && *style == cell.style =>
+                    {
+                        symbols.push_str(&cell.symbol);
+                        *end += width;
+                    }
+                    _ => {
+                        if let Some(run) = run.take() {
+                            position = Some(draw_run(tty, run, position)?);
+                        }
+                        run = Some(((x, y), x + width, cell.style, cell.symbol.clone()));
+                    }
+                }
+            }
+        }
+        if let Some(run) = run {
+            draw_run(tty, run, position)?;
+        }
+
+        tty.queue(cursor::RestorePosition)?
+            .queue(terminal::EndSynchronizedUpdate)?;
+        tty.flush()
+    }
+
+    /// Writes all cells line by line with their styles, without moving the cursor,
+    /// to print the buffer along with other output.
+    pub fn write_lines(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for line in self.cells.chunks(self.area.width.max(1) as usize) {
+            let mut run: Option<(ContentStyle, String)> = None;
+            for cell in line {
+                match &mut run {
+                    Some((style, symbols)) if *style == cell.style => {
+                        symbols.push_str(&cell.symbol);
+                    }
+                    _ => {
+                        if let Some((style, symbols)) = run.take() {
+                            out.queue(PrintStyledContent(StyledContent::new(style, symbols)))?;
+                        }
+                        run = Some((cell.style, cell.symbol.clone()));
+                    }
+                }
+            }
+            if let Some((style, symbols)) = run {
+                out.queue(PrintStyledContent(StyledContent::new(style, symbols)))?;
+            }
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+}
+
+/// Shows the text of the cells line by line, without styles, to inspect what was drawn.
+impl Display for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, line) in self
+            .cells
+            .chunks(self.area.width.max(1) as usize)
+            .enumerate()
+        {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for cell in line {
+                write!(f, "{}", cell.symbol)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a run of cells, moving the cursor there unless it already is, and returns
+/// where the cursor ends up.
+fn draw_run(
+    tty: &mut impl Write,
+    ((x, y), end, style, symbols): ((u16, u16), u16, ContentStyle, String),
+    position: Option<(u16, u16)>,
+) -> std::io::Result<(u16, u16)> {
+    if position != Some((x, y)) {
+        tty.queue(cursor::MoveTo(x, y))?;
+    }
+    tty.queue(PrintStyledContent(StyledContent::new(style, symbols)))?;
+    Ok((end, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    const AREA: Area = Area {
+        x: 2,
+        y: 1,
+        width: 6,
+        height: 2,
+    };
+
+    fn drawn(buffer: &Buffer, previous: Option<&Buffer>) -> String {
+        let mut out = Vec::new();
+        buffer.draw(&mut out, previous).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_set_string() {
+        let mut buffer = Buffer::empty(AREA);
+        assert_eq!(buffer.set_string(2, 1, "ab日", ContentStyle::new()), 6);
+        // Cut off at the edge.
+        buffer.set_string(5, 2, "e\u{301}日本", ContentStyle::new());
+        assert_eq!(buffer.to_string(), "ab日  \n   e\u{301}日");
+        assert_eq!(buffer.cell(5, 1).unwrap().symbol, "");
+        assert_eq!(buffer.cell(8, 1), None);
+
+        // Overwriting half of a wide character blanks the other half.
+        buffer.set_string(5, 1, "x", ContentStyle::new());
+        assert_eq!(buffer.to_string().lines().next(), Some("ab x  "));
+        buffer.set_string(6, 2, "y", ContentStyle::new());
+        assert_eq!(buffer.to_string().lines().last(), Some("   e\u{301}y "));
+
+        // Joined emoji take up a single wide cell.
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(buffer.set_string(2, 2, family, ContentStyle::new()), 4);
+        assert_eq!(buffer.cell(2, 2).unwrap().symbol, family);
+        assert_eq!(buffer.cell(3, 2).unwrap().symbol, "");
+    }
+
+    #[test]
+    fn test_draw_only_changes() {
+        let mut previous = Buffer::empty(AREA);
+        previous.set_styled(2, 1, &"abc".red());
+        let full = drawn(&previous, None);
+        assert!(full.starts_with("\x1b[?2026h"), "{full:?}");
+        assert!(full.ends_with("\x1b[?2026l"), "{full:?}");
+        assert!(full.contains("abc"), "{full:?}");
+        // Both lines are written, each with a single move.
+        assert_eq!(full.matches('H').count(), 2, "{full:?}");
+        assert!(full.contains("\x1b[3;3H"), "{full:?}");
+
+        let mut next = previous.clone();
+        assert!(!drawn(&next, Some(&previous)).contains('H'));
+
+        next.set_styled(3, 1, &"xy".red());
+        next.set_styled(2, 2, &"z".blue());
+        let diff = drawn(&next, Some(&previous));
+        assert!(diff.contains("\x1b[2;4H"), "{diff:?}");
+        assert!(diff.contains("xy"), "{diff:?}");
+        assert!(diff.contains("\x1b[3;3H"), "{diff:?}");
+        assert!(!diff.contains('a'), "{diff:?}");
+
+        // A buffer of another area is drawn completely.
+        let moved = Buffer::empty(Area { y: 0, ..AREA });
+        assert!(drawn(&moved, Some(&previous)).contains("\x1b[1;3H"));
+    }
+}
@@ -0,0 +1,455 @@
+//! Colors of themes and their approximation on terminals without truecolor support.
+
+use std::{borrow::Cow, fmt};
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor, value::MapAccessDeserializer},
+};
+
+/// A color of a theme.
+///
+/// In theme files, colors can be written as `{ r = 30, g = 30, b = 46 }` tables,
+/// `"#1e1e2e"` hex strings, ANSI names like `"blue"` or `"bright-black"`,
+/// or indices into the 256 color palette.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Rgb { r: u8, g: u8, b: u8 },
+    Named(NamedColor),
+    Indexed(u8),
+}
+
+/// The 16 ANSI colors, whose actual values depend on the terminal's palette.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamedColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+/// Whether leadr renders in color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Use colors unless `NO_COLOR` is set or `TERM` is `dumb`.
+    #[default]
+    Auto,
+    /// Always use colors.
+    Always,
+    /// Never use colors, distinguish elements by bold, underlined and reversed text instead.
+    Never,
+}
+
+impl ColorMode {
+    /// Resolves the mode against `NO_COLOR` and `TERM`.
+    pub fn use_color(self) -> bool {
+        self.resolve(
+            std::env::var("NO_COLOR").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn resolve(self, no_color: Option<&str>, term: Option<&str>) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            // See https://no-color.org: only a non-empty value disables colors.
+            ColorMode::Auto => no_color.is_none_or(str::is_empty) && term != Some("dumb"),
+        }
+    }
+}
+
+/// The colors a terminal is able to display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl NamedColor {
+    const ALL: [NamedColor; 16] = [
+        NamedColor::Black,
+        NamedColor::Red,
+        NamedColor::Green,
+        NamedColor::Yellow,
+        NamedColor::Blue,
+        NamedColor::Magenta,
+        NamedColor::Cyan,
+        NamedColor::White,
+        NamedColor::BrightBlack,
+        NamedColor::BrightRed,
+        NamedColor::BrightGreen,
+        NamedColor::BrightYellow,
+        NamedColor::BrightBlue,
+        NamedColor::BrightMagenta,
+        NamedColor::BrightCyan,
+        NamedColor::BrightWhite,
+    ];
+
+    fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    /// The typical (xterm) value of this color, used to find the closest match for RGB colors.
+    fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            NamedColor::Black => (0, 0, 0),
+            NamedColor::Red => (205, 0, 0),
+            NamedColor::Green => (0, 205, 0),
+            NamedColor::Yellow => (205, 205, 0),
+            NamedColor::Blue => (0, 0, 238),
+            NamedColor::Magenta => (205, 0, 205),
+            NamedColor::Cyan => (0, 205, 205),
+            NamedColor::White => (229, 229, 229),
+            NamedColor::BrightBlack => (127, 127, 127),
+            NamedColor::BrightRed => (255, 0, 0),
+            NamedColor::BrightGreen => (0, 255, 0),
+            NamedColor::BrightYellow => (255, 255, 0),
+            NamedColor::BrightBlue => (92, 92, 255),
+            NamedColor::BrightMagenta => (255, 0, 255),
+            NamedColor::BrightCyan => (0, 255, 255),
+            NamedColor::BrightWhite => (255, 255, 255),
+        }
+    }
+}
+
+impl ColorSupport {
+    /// Detects the color support of the terminal from `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        Self::from_env(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match term {
+            Some(term) if term.contains("truecolor") || term.contains("direct") => {
+                ColorSupport::TrueColor
+            }
+            Some(term) if term.contains("256color") => ColorSupport::Ansi256,
+            Some("linux" | "ansi" | "cons25" | "dumb" | "screen" | "tmux")
+            | Some("vt100" | "vt220" | "xterm-color" | "rxvt") => ColorSupport::Ansi16,
+            // Most other terminals handle truecolor fine, which is what leadr always used.
+            _ => ColorSupport::TrueColor,
+        }
+    }
+}
+
+/// The levels of each channel in the 6x6x6 color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Color {
+    /// Returns the closest color the terminal is able to display.
+    pub fn approximate(self, support: ColorSupport) -> Self {
+        match (self, support) {
+            (_, ColorSupport::TrueColor) | (Color::Named(_), _) => self,
+            (Color::Indexed(index), ColorSupport::Ansi256) => Color::Indexed(index),
+            (Color::Rgb { r, g, b }, ColorSupport::Ansi256) => {
+                Color::Indexed(closest_indexed(r, g, b))
+            }
+            (Color::Indexed(index), ColorSupport::Ansi16) => match NamedColor::from_index(index) {
+                Some(named) => Color::Named(named),
+                None => {
+                    let (r, g, b) = indexed_rgb(index);
+                    Color::Named(closest_named(r, g, b))
+                }
+            },
+            (Color::Rgb { r, g, b }, ColorSupport::Ansi16) => Color::Named(closest_named(r, g, b)),
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self, String> {
+        if let Some(hex) = value.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(|| {
+                format!("invalid hex color '{value}', expected \"#rrggbb\" or \"#rgb\"")
+            });
+        }
+        NamedColor::deserialize(de::value::StrDeserializer::<de::value::Error>::new(value))
+            .map(Color::Named)
+            .map_err(|_| {
+                format!(
+                    "invalid color '{value}', expected a hex color like \"#1e1e2e\" \
+                     or an ANSI color name like \"blue\" or \"bright-black\""
+                )
+            })
+    }
+}
+
+impl From<Color> for crossterm::style::Color {
+    fn from(color: Color) -> Self {
+        use crossterm::style::Color as C;
+
+        match color {
+            Color::Rgb { r, g, b } => C::Rgb { r, g, b },
+            Color::Indexed(index) => C::AnsiValue(index),
+            Color::Named(named) => match named {
+                NamedColor::Black => C::Black,
+                NamedColor::Red => C::DarkRed,
+                NamedColor::Green => C::DarkGreen,
+                NamedColor::Yellow => C::DarkYellow,
+                NamedColor::Blue => C::DarkBlue,
+                NamedColor::Magenta => C::DarkMagenta,
+                NamedColor::Cyan => C::DarkCyan,
+                NamedColor::White => C::Grey,
+                NamedColor::BrightBlack => C::DarkGrey,
+                NamedColor::BrightRed => C::Red,
+                NamedColor::BrightGreen => C::Green,
+                NamedColor::BrightYellow => C::Yellow,
+                NamedColor::BrightBlue => C::Blue,
+                NamedColor::BrightMagenta => C::Magenta,
+                NamedColor::BrightCyan => C::Cyan,
+                NamedColor::BrightWhite => C::White,
+            },
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    if !hex.is_ascii() {
+        return None;
+    }
+    match hex.len() {
+        6 => Some(Color::Rgb {
+            r: channel(&hex[0..2])?,
+            g: channel(&hex[2..4])?,
+            b: channel(&hex[4..6])?,
+        }),
+        3 => Some(Color::Rgb {
+            r: channel(&hex[0..1])? * 17,
+            g: channel(&hex[1..2])? * 17,
+            b: channel(&hex[2..3])? * 17,
+        }),
+        _ => None,
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// The RGB value of an entry of the 256 color palette.
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => NamedColor::ALL[index as usize].rgb(),
+        16..232 => {
+            let cube = index - 16;
+            (
+                CUBE_LEVELS[(cube / 36) as usize],
+                CUBE_LEVELS[(cube / 6 % 6) as usize],
+                CUBE_LEVELS[(cube % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+/// Finds the closest entry of the color cube or the grayscale ramp of the 256 color palette.
+/// The first 16 entries are skipped since their values depend on the terminal's palette.
+fn closest_indexed(r: u8, g: u8, b: u8) -> u8 {
+    (16..=255)
+        .min_by_key(|&index| distance(indexed_rgb(index), (r, g, b)))
+        .unwrap_or(16)
+}
+
+fn closest_named(r: u8, g: u8, b: u8) -> NamedColor {
+    NamedColor::ALL
+        .into_iter()
+        .min_by_key(|named| distance(named.rgb(), (r, g, b)))
+        .unwrap_or(NamedColor::White)
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct RgbTable {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Color::Rgb { r, g, b } => RgbTable { r, g, b }.serialize(serializer),
+            Color::Named(named) => named.serialize(serializer),
+            Color::Indexed(index) => serializer.serialize_u8(index),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ColorVisitor;
+
+        impl<'de> Visitor<'de> for ColorVisitor {
+            type Value = Color;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(
+                    "a color as { r, g, b } table, hex string, ANSI color name or 256 color index",
+                )
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Color, E> {
+                Color::parse(value).map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Color, E> {
+                u8::try_from(value).map(Color::Indexed).map_err(|_| {
+                    E::custom(format!(
+                        "invalid color index {value}, expected a value between 0 and 255"
+                    ))
+                })
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Color, E> {
+                self.visit_i64(i64::try_from(value).unwrap_or(i64::MAX))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Color, A::Error> {
+                let RgbTable { r, g, b } = RgbTable::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(Color::Rgb { r, g, b })
+            }
+        }
+
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+impl JsonSchema for Color {
+    fn schema_name() -> Cow<'static, str> {
+        "Color".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A color as { r, g, b } table, \"#rrggbb\" hex string, ANSI color name or 256 color index.",
+            "anyOf": [
+                generator.subschema_for::<RgbTable>(),
+                { "type": "string", "pattern": "^#([0-9a-fA-F]{3}|[0-9a-fA-F]{6})$" },
+                generator.subschema_for::<NamedColor>(),
+                { "type": "integer", "minimum": 0, "maximum": 255 }
+            ]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        color: Color,
+    }
+
+    fn parse(value: &str) -> Result<Color, toml::de::Error> {
+        toml::from_str::<Wrapper>(&format!("color = {value}")).map(|wrapper| wrapper.color)
+    }
+
+    #[test]
+    fn test_parse_color_formats() {
+        let expected = Color::Rgb {
+            r: 30,
+            g: 30,
+            b: 46,
+        };
+        assert_eq!(parse("{ r = 30, g = 30, b = 46 }").unwrap(), expected);
+        assert_eq!(parse("\"#1e1e2e\"").unwrap(), expected);
+        assert_eq!(
+            parse("\"#fff\"").unwrap(),
+            Color::Rgb {
+                r: 255,
+                g: 255,
+                b: 255
+            }
+        );
+        assert_eq!(
+            parse("\"bright-black\"").unwrap(),
+            Color::Named(NamedColor::BrightBlack)
+        );
+        assert_eq!(parse("236").unwrap(), Color::Indexed(236));
+
+        assert!(parse("\"#12345\"").is_err());
+        assert!(parse("\"purple\"").is_err());
+        assert!(parse("256").is_err());
+    }
+
+    #[test]
+    fn test_approximate() {
+        let crust = Color::Rgb {
+            r: 17,
+            g: 17,
+            b: 27,
+        };
+        assert_eq!(crust.approximate(ColorSupport::TrueColor), crust);
+        assert_eq!(
+            crust.approximate(ColorSupport::Ansi256),
+            Color::Indexed(233)
+        );
+        assert_eq!(
+            crust.approximate(ColorSupport::Ansi16),
+            Color::Named(NamedColor::Black)
+        );
+
+        let blue = Color::Rgb { r: 0, g: 0, b: 255 };
+        assert_eq!(blue.approximate(ColorSupport::Ansi256), Color::Indexed(21));
+        assert_eq!(
+            Color::Indexed(4).approximate(ColorSupport::Ansi16),
+            Color::Named(NamedColor::Blue)
+        );
+        assert_eq!(
+            Color::Indexed(196).approximate(ColorSupport::Ansi16),
+            Color::Named(NamedColor::BrightRed)
+        );
+    }
+
+    #[test]
+    fn test_detect_color_support() {
+        use ColorSupport::*;
+
+        assert_eq!(
+            ColorSupport::from_env(Some("truecolor"), Some("linux")),
+            TrueColor
+        );
+        assert_eq!(ColorSupport::from_env(None, Some("tmux-256color")), Ansi256);
+        assert_eq!(ColorSupport::from_env(None, Some("linux")), Ansi16);
+        assert_eq!(ColorSupport::from_env(None, Some("screen")), Ansi16);
+        assert_eq!(ColorSupport::from_env(None, Some("xterm-kitty")), TrueColor);
+    }
+
+    #[test]
+    fn test_resolve_color_mode() {
+        use ColorMode::*;
+
+        assert!(Auto.resolve(None, Some("xterm-256color")));
+        assert!(Auto.resolve(Some(""), Some("xterm-256color")));
+        assert!(!Auto.resolve(Some("1"), Some("xterm-256color")));
+        assert!(!Auto.resolve(None, Some("dumb")));
+        assert!(Always.resolve(Some("1"), Some("dumb")));
+        assert!(!Never.resolve(None, Some("xterm-256color")));
+    }
+}
@@ -0,0 +1,376 @@
+use crossterm::style::StyledContent;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    InsertType, Mapping, Symbols, Theme,
+    ui::{area::Area, buffer::Buffer, panel::BorderType, text, theme::Role},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// Whether to show the full command of the mapping the typed sequence leads to.
+    pub enabled: bool,
+    /// Maximum number of lines the command is wrapped over before it is cut off.
+    pub max_lines: u16,
+}
+
+impl std::default::Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_lines: 3,
+        }
+    }
+}
+
+/// Kinds of tokens a command is highlighted by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Space,
+    Program,
+    Argument,
+    Flag,
+    String,
+    Variable,
+    Placeholder,
+    Operator,
+}
+
+impl Token {
+    fn role(self) -> Role {
+        match self {
+            Token::Space => Role::Blank,
+            Token::Program => Role::Key,
+            Token::Argument => Role::Text,
+            Token::Flag | Token::Variable => Role::Flags,
+            Token::String => Role::Description,
+            Token::Placeholder => Role::Prefix,
+            Token::Operator => Role::Arrow,
+        }
+    }
+}
+
+const OPERATORS: &[&str] = &["&&", "||", ">>", "|", ";", "&", ">", "<"];
+const PLACEHOLDERS: &[&str] = &["#CURSOR", "#COMMAND"];
+
+/// Splits a shell command into tokens for highlighting. This is no shell parser,
+/// anything it doesn't understand just ends up as an argument.
+fn tokenize(command: &str) -> Vec<(String, Token)> {
+    let mut tokens = Vec::new();
+    let mut rest = command;
+    let mut expect_program = true;
+
+    while let Some(c) = rest.chars().next() {
+        let (length, token) = if c.is_whitespace() {
+            let length = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            (length, Token::Space)
+        } else if let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            (operator.len(), Token::Operator)
+        } else if let Some(placeholder) = PLACEHOLDERS.iter().find(|p| rest.starts_with(**p)) {
+            (placeholder.len(), Token::Placeholder)
+        } else if c == '"' || c == '\'' {
+            (quoted_length(rest, c), Token::String)
+        } else if c == '$' {
+            (variable_length(rest), Token::Variable)
+        } else {
+            let length = rest
+                .find(|c: char| c.is_whitespace() || "\"'$|&;<>".contains(c))
+                .unwrap_or(rest.len())
+                .max(c.len_utf8());
+            let token = if expect_program {
+                Token::Program
+            } else if c == '-' {
+                Token::Flag
+            } else {
+                Token::Argument
+            };
+            (length, token)
+        };
+
+        match token {
+            Token::Operator => expect_program = matches!(&rest[..length], "&&" | "||" | "|" | ";"),
+            Token::Space => {}
+            _ => expect_program = false,
+        }
+        tokens.push((rest[..length].to_string(), token));
+        rest = &rest[length..];
+    }
+
+    tokens
+}
+
+/// Length of the string starting with the `quote` at the start of `text`, including both quotes.
+fn quoted_length(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if quote == '"' => escaped = !escaped,
+            c if c == quote && !escaped => return i + c.len_utf8(),
+            _ => escaped = false,
+        }
+    }
+    text.len()
+}
+
+/// Length of the `$NAME`, `${NAME}` or `$(command)` at the start of `text`.
+fn variable_length(text: &str) -> usize {
+    let closing = match text[1..].chars().next() {
+        Some('{') => '}',
+        Some('(') => ')',
+        _ => {
+            return 1 + text[1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(text.len() - 1);
+        }
+    };
+    text.find(closing).map_or(text.len(), |end| end + 1)
+}
+
+/// Describes what inserting a mapping does, e.g. "appends to the prompt, executes it".
+fn describe(mapping: &Mapping) -> String {
+    let mut parts = vec![match mapping.insert_type {
+        InsertType::Replace => "replaces the prompt",
+        InsertType::Insert => "inserts at the cursor",
+        InsertType::Prepend => "prepends to the prompt",
+        InsertType::Append => "appends to the prompt",
+        InsertType::Surround => "surrounds the prompt",
+    }];
+    if mapping.evaluate {
+        parts.push("evaluates the command first");
+    }
+    if mapping.execute {
+        parts.push("executes it");
+    }
+    parts.join(", ")
+}
+
+/// The full command, insert type, flags and source file of a single mapping,
+/// shown below the entries of the panel.
+pub struct CommandPreview {
+    lines: Vec<Vec<StyledContent<String>>>,
+}
+
+impl CommandPreview {
+    pub fn new(
+        sequence: &str,
+        mapping: &Mapping,
+        width: u16,
+        config: &PreviewConfig,
+        symbols: &Symbols,
+        theme: &Theme,
+    ) -> Self {
+        let width = width as usize;
+        let (_, _, _, _, horizontal, _) = symbols.border(&BorderType::Square);
+        let mut lines = vec![vec![
+            theme.style(horizontal.to_string().repeat(width), Role::Border),
+        ]];
+
+        let mut command_lines = wrap(&tokenize(&mapping.command), width);
+        if command_lines.len() > config.max_lines.max(1) as usize {
+            command_lines.truncate(config.max_lines.max(1) as usize);
+            if let Some(last) = command_lines.last_mut() {
+                ellipsize(last, width, &symbols.ellipsis);
+            }
+        }
+        lines.extend(command_lines.into_iter().map(|line| {
+            line.into_iter()
+                .map(|(text, token)| theme.style(text, token.role()))
+                .collect()
+        }));
+
+        let details = format!("{sequence}: {}", describe(mapping));
+        let source = mapping
+            .source_file
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        // The source is cut off at the front, where the least interesting part of the path is.
+        let details = text::truncate(&details, width);
+        let room = width.saturating_sub(text::width(details) + 2);
+        let source = text::truncate_start_with_ellipsis(&source, room, &symbols.ellipsis);
+        let padding = width.saturating_sub(text::width(details) + text::width(&source));
+        lines.push(vec![
+            theme.style(details.to_string(), Role::Muted),
+            theme.style(" ".repeat(padding), Role::Blank),
+            theme.style(source, Role::Muted),
+        ]);
+
+        Self { lines }
+    }
+
+    /// Number of lines the preview takes up.
+    pub fn height(&self) -> u16 {
+        self.lines.len() as u16
+    }
+
+    /// Draws the preview at the top of `area`, leaving out lines that don't fit.
+    pub fn render(&self, frame: &mut Buffer, area: &Area) {
+        for (line, parts) in (area.y..area.y + area.height).zip(&self.lines) {
+            let mut x = area.x;
+            for part in parts {
+                x = frame.set_styled(x, line, part);
+            }
+        }
+    }
+}
+
+/// Wraps tokens into lines of at most `width` cells, splitting tokens where necessary.
+fn wrap(tokens: &[(String, Token)], width: usize) -> Vec<Vec<(String, Token)>> {
+    let mut lines = vec![Vec::new()];
+    let mut used = 0;
+    for (text, token) in tokens {
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            let (mut part, mut remainder) = text::split_at_width(rest, width.saturating_sub(used));
+            // A wide character that doesn't fit at the end of a line starts the next one.
+            if used + text::width(part) > width && used > 0 {
+                (part, remainder) = ("", rest);
+            }
+            if part.is_empty() {
+                lines.push(Vec::new());
+                used = 0;
+                continue;
+            }
+            used += text::width(part);
+            lines.last_mut().unwrap().push((part.to_string(), *token));
+            rest = remainder;
+        }
+    }
+    lines
+}
+
+/// Makes room for `ellipsis` at the end of a line by cutting off as much as necessary.
+fn ellipsize(line: &mut Vec<(String, Token)>, width: usize, ellipsis: &str) {
+    let needed = text::width(ellipsis);
+    let mut used: usize = line.iter().map(|(text, _)| text::width(text)).sum();
+    while used + needed > width
+        && let Some((text, _)) = line.last_mut()
+    {
+        let cut = text::truncate(
+            text,
+            text::width(text).saturating_sub(used + needed - width),
+        );
+        used -= text::width(text) - text::width(cut);
+        text.truncate(cut.len());
+        if text.is_empty() {
+            line.pop();
+        }
+    }
+    line.push((ellipsis.into(), Token::Argument));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(command: &str) -> Vec<(String, Token)> {
+        // Whitespace is left out to keep the expectations short.
+        tokenize(command)
+            .into_iter()
+            .filter(|(_, token)| *token != Token::Space)
+            .collect()
+    }
+
+    fn expected(tokens: &[(&str, Token)]) -> Vec<(String, Token)> {
+        tokens
+            .iter()
+            .map(|(text, token)| (text.to_string(), *token))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokens("git commit -m \"#CURSOR\" && echo $HOME | wc -l"),
+            expected(&[
+                ("git", Token::Program),
+                ("commit", Token::Argument),
+                ("-m", Token::Flag),
+                ("\"#CURSOR\"", Token::String),
+                ("&&", Token::Operator),
+                ("echo", Token::Program),
+                ("$HOME", Token::Variable),
+                ("|", Token::Operator),
+                ("wc", Token::Program),
+                ("-l", Token::Flag),
+            ])
+        );
+        assert_eq!(
+            tokens("sudo #COMMAND > $(date +%F).log"),
+            expected(&[
+                ("sudo", Token::Program),
+                ("#COMMAND", Token::Placeholder),
+                (">", Token::Operator),
+                ("$(date +%F)", Token::Variable),
+                (".log", Token::Argument),
+            ])
+        );
+        // Unterminated strings run until the end.
+        assert_eq!(
+            tokens("echo 'oops"),
+            expected(&[("echo", Token::Program), ("'oops", Token::String)])
+        );
+    }
+
+    #[test]
+    fn test_wrap_and_cut_off() {
+        let mapping = Mapping {
+            command: "echo abcdefghij".into(),
+            execute: true,
+            insert_type: InsertType::Append,
+            ..Default::default()
+        };
+        let theme = Theme::catppuccin_mocha().monochrome();
+        let text = |preview: &CommandPreview| -> Vec<String> {
+            preview
+                .lines
+                .iter()
+                .map(|parts| parts.iter().map(|part| part.content().as_str()).collect())
+                .collect()
+        };
+
+        let config = PreviewConfig::default();
+        let preview = CommandPreview::new("y", &mapping, 6, &config, &Symbols::default(), &theme);
+        assert_eq!(
+            text(&preview)[1..4],
+            ["echo a", "bcdefg", "hij"].map(String::from)
+        );
+        assert!(text(&preview)[4].starts_with("y: app"));
+
+        let config = PreviewConfig {
+            max_lines: 2,
+            ..config
+        };
+        let preview = CommandPreview::new("y", &mapping, 6, &config, &Symbols::default(), &theme);
+        assert_eq!(preview.height(), 4);
+        assert_eq!(text(&preview)[2], "bcdef…");
+        assert_eq!(describe(&mapping), "appends to the prompt, executes it");
+    }
+
+    #[test]
+    fn test_wrap_wide_characters() {
+        let text = |lines: Vec<Vec<(String, Token)>>| -> Vec<String> {
+            lines
+                .into_iter()
+                .map(|parts| parts.into_iter().map(|(text, _)| text).collect())
+                .collect()
+        };
+        // "日" doesn't fit behind "echo " and moves to the next line as a whole.
+        assert_eq!(
+            text(wrap(&tokenize("echo 日本語🚀"), 6)),
+            ["echo ", "日本語", "🚀"].map(String::from)
+        );
+        assert_eq!(
+            text(wrap(&tokenize("echo e\u{301}e\u{301}"), 6)),
+            ["echo e\u{301}", "e\u{301}"].map(String::from)
+        );
+
+        let mut line = wrap(&tokenize("日本語"), 6).remove(0);
+        ellipsize(&mut line, 6, "…");
+        assert_eq!(text(vec![line]), ["日本…"].map(String::from));
+    }
+}
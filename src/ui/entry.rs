@@ -1,8 +1,8 @@
-use std::io::Write;
-
-use crossterm::style::Stylize;
-
-use crate::{InsertType, Mapping, Symbols, Theme, mappings::MatchType};
+use crate::{
+    InsertType, Mapping, Symbols, Theme,
+    mappings::MatchType,
+    ui::{buffer::Buffer, text, theme::Role},
+};
 
 pub struct Entry {
     pub styled_parts: Vec<crossterm::style::StyledContent<String>>,
@@ -15,8 +15,9 @@ impl Entry {
         width: u16,
         symbols: &Symbols,
         theme: &Theme,
+        highlighted: bool,
     ) -> Self {
-        let (mut label, flags, is_prefix) = match match_type {
+        let (label, flags, is_prefix, icon, color) = match match_type {
             MatchType::Exact(mapping) => {
                 let label = mapping
                     .description
@@ -24,59 +25,58 @@ impl Entry {
                     .unwrap_or(&mapping.command)
                     .to_string();
                 let flags = format_flags(mapping, symbols);
-                (label, flags, false)
+                (label, flags, false, mapping.icon.as_deref(), mapping.color)
             }
-            MatchType::Prefix(count) => (format!("+{} mappings", count), " ".repeat(5), true),
-            MatchType::None => ("(invalid)".into(), "".into(), true),
+            MatchType::Prefix { count, group } => (
+                format!("+{} mappings", count),
+                " ".repeat(5),
+                true,
+                group.and_then(|group| group.icon.as_deref()),
+                group.and_then(|group| group.color),
+            ),
+            MatchType::None => ("(invalid)".into(), "".into(), true, None, None),
         };
+        let icon = icon.map(|icon| format!("{icon} ")).unwrap_or_default();
 
-        let raw_entry = format!("{} → {} {}", key, label, flags);
-        let raw_entry_width = raw_entry.chars().count() as u16;
-
-        let mut spacing = " ".to_string();
-        if raw_entry_width > width {
-            let entry_overflow = raw_entry_width - width;
-            label = label
-                .chars()
-                .take(label.chars().count() - entry_overflow as usize - 2)
-                .collect::<String>()
-                + "…";
-        } else {
-            let entry_underflow = width - raw_entry_width;
-            spacing = " ".repeat(entry_underflow as usize);
-        };
-        let styled_label = if is_prefix {
-            label
-                .with(theme.text_primary.into())
-                .on(theme.background.into())
+        let arrow = format!(" {} ", symbols.arrow);
+
+        // The label gets whatever the other parts and the space before the flags leave over,
+        // and the spacing fills it up, so entries take up `width` cells exactly.
+        let fixed_width = [key, &arrow, &icon, " ", &flags]
+            .map(text::width)
+            .iter()
+            .sum::<usize>();
+        let label_width = (width as usize).saturating_sub(fixed_width);
+        let label = text::truncate_with_ellipsis(&label, label_width, &symbols.ellipsis);
+        let spacing = " ".repeat(1 + label_width - text::width(&label));
+        let label_role = if is_prefix {
+            Role::Prefix
         } else {
-            label
-                .with(theme.text_highlight_secondary.into())
-                .on(theme.background.into())
+            Role::Description
         };
 
-        let styled_parts = vec![
-            key.to_string()
-                .with(theme.text_primary.into())
-                .on(theme.background.into()),
-            format!(" {} ", symbols.arrow)
-                .to_string()
-                .with(theme.text_secondary.into())
-                .on(theme.background.into()),
-            styled_label,
-            spacing.on(theme.background.into()),
-            flags
-                .with(theme.text_highlight_primary.into())
-                .on(theme.background.into()),
+        let mut styled_parts = vec![
+            theme.style(key.to_string(), Role::Key),
+            theme.style(arrow, Role::Arrow),
+            theme.style_with(icon, label_role, color),
+            theme.style_with(label, label_role, color),
+            theme.style(spacing, Role::Blank),
+            theme.style(flags, Role::Flags),
         ];
+        if highlighted {
+            for part in &mut styled_parts {
+                theme.highlight(part.style_mut());
+            }
+        }
         Self { styled_parts }
     }
 
-    pub fn to_tty(&self, tty: &mut std::fs::File) -> std::io::Result<()> {
+    /// Draws the entry into `frame`, starting at the given cell.
+    pub fn render(&self, frame: &mut Buffer, x: u16, y: u16) {
+        let mut x = x;
         for part in &self.styled_parts {
-            write!(tty, "{}", part)?;
+            x = frame.set_styled(x, y, part);
         }
-        Ok(())
     }
 }
 
@@ -105,3 +105,65 @@ fn format_flags(mapping: &Mapping, symbols: &Symbols) -> String {
 
     flags.join(" ")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(description: &str, icon: Option<&str>, width: u16) -> String {
+        let mapping = Mapping {
+            command: "true".into(),
+            description: Some(description.into()),
+            icon: icon.map(String::from),
+            ..Default::default()
+        };
+        let theme = Theme::default().monochrome();
+        let entry = Entry::new(
+            "a",
+            MatchType::Exact(&mapping),
+            width,
+            &Symbols::default(),
+            &theme,
+            false,
+        );
+        entry
+            .styled_parts
+            .iter()
+            .map(|part| part.content().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_entries_fill_their_width() {
+        for description in [
+            "ascii",
+            "日本語の説明文です",
+            "🚀🚀🚀🚀🚀🚀🚀🚀",
+            "e\u{301}".repeat(20).as_str(),
+        ] {
+            for icon in [None, Some("🚀")] {
+                for width in [14, 20, 30] {
+                    let entry = rendered(description, icon, width);
+                    assert_eq!(text::width(&entry), width as usize, "{entry:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_labels_are_cut_between_characters() {
+        // Key and arrow take up 4 cells, the flags and the space before them 6.
+        let entry = rendered("日本語", None, 15);
+        assert!(entry.starts_with("a → 日本… "), "{entry:?}");
+        let entry = rendered("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", None, 14);
+        assert!(
+            entry.starts_with("a → e\u{301}e\u{301}e\u{301}… "),
+            "{entry:?}"
+        );
+    }
+
+    #[test]
+    fn test_too_narrow_columns_do_not_panic() {
+        assert!(text::width(&rendered("description", Some("🚀"), 3)) > 3);
+    }
+}
@@ -0,0 +1,123 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::ui::theme::Role;
+
+/// Footer of the panel, built from templates with placeholders like `{sequence}`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FooterConfig {
+    /// Whether to show the footer. Without it, the panel is two lines shorter.
+    pub enabled: bool,
+    /// Template aligned to the left.
+    pub left: String,
+    /// Template centered in the footer.
+    pub center: String,
+    /// Template aligned to the right.
+    pub right: String,
+}
+
+impl std::default::Default for FooterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            left: "{sequence}".into(),
+            center: "{cancel_key} close  {back_key} back".into(),
+            right: "{page}".into(),
+        }
+    }
+}
+
+/// The values the placeholders of a footer template are replaced by.
+pub struct FooterValues<'a> {
+    /// Shown in front of the typed sequence.
+    pub sequence_begin: &'a str,
+    /// The typed sequence in Vim notation.
+    pub sequence: String,
+    /// The typed keys, each with the icon of its group.
+    pub breadcrumb: String,
+    /// Number of mappings starting with the typed sequence.
+    pub matches: usize,
+    /// The page indicator, empty if all continuations fit.
+    pub page: String,
+    pub cancel_key: String,
+    pub back_key: String,
+}
+
+impl FooterValues<'_> {
+    /// Returns the styled parts a placeholder expands to, or `None` for unknown placeholders.
+    fn expand(&self, name: &str) -> Option<Vec<(String, Role)>> {
+        let parts = match name {
+            "sequence" => vec![
+                (self.sequence_begin.to_string(), Role::Muted),
+                (self.sequence.clone(), Role::Sequence),
+            ],
+            "breadcrumb" => vec![(self.breadcrumb.clone(), Role::Prefix)],
+            "matches" => vec![(self.matches.to_string(), Role::Muted)],
+            "page" => vec![(self.page.clone(), Role::Muted)],
+            "cancel_key" => vec![(self.cancel_key.clone(), Role::Key)],
+            "back_key" => vec![(self.back_key.clone(), Role::Key)],
+            _ => return None,
+        };
+        Some(parts)
+    }
+}
+
+/// Fills in the placeholders of `template`. Unknown placeholders are kept as they are.
+pub fn render(template: &str, values: &FooterValues) -> Vec<(String, Role)> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + length];
+        match values.expand(name) {
+            Some(expanded) => {
+                parts.push((rest[..start].to_string(), Role::Text));
+                parts.extend(expanded);
+            }
+            None => parts.push((rest[..=start + length].to_string(), Role::Text)),
+        }
+        rest = &rest[start + length + 1..];
+    }
+    parts.push((rest.to_string(), Role::Text));
+    parts.retain(|(text, _)| !text.is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let values = FooterValues {
+            sequence_begin: ">",
+            sequence: "g<Space>".into(),
+            breadcrumb: "g › <Space>".into(),
+            matches: 3,
+            page: String::new(),
+            cancel_key: "<Esc>".into(),
+            back_key: "<BS>".into(),
+        };
+        let text = |template: &str| -> String {
+            render(template, &values)
+                .into_iter()
+                .map(|(text, _)| text)
+                .collect()
+        };
+
+        assert_eq!(text("{sequence}"), ">g<Space>");
+        assert_eq!(text("{matches} matches{page}"), "3 matches");
+        assert_eq!(
+            text("{cancel_key} close  {back_key} back"),
+            "<Esc> close  <BS> back"
+        );
+        assert_eq!(text("{unknown} {breadcrumb} {"), "{unknown} g › <Space> {");
+        assert_eq!(
+            render("{back_key}!", &values),
+            vec![("<BS>".into(), Role::Key), ("!".into(), Role::Text)]
+        );
+    }
+}
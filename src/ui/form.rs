@@ -0,0 +1,128 @@
+use crate::Mappings;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FormField {
+    Sequence,
+    Description,
+}
+
+/// State of the form used to save the current command line as a new mapping.
+pub struct SaveForm {
+    pub command: String,
+    pub sequence: String,
+    pub description: String,
+    pub field: FormField,
+    pub error: Option<String>,
+}
+
+/// Whether a sequence entered in the form can be used for a new mapping.
+pub enum SequenceStatus {
+    Empty,
+    Conflict(String),
+    Available,
+}
+
+impl SaveForm {
+    pub fn new(command: String) -> Self {
+        Self {
+            command,
+            sequence: String::new(),
+            description: String::new(),
+            field: FormField::Sequence,
+            error: None,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.error = None;
+        match self.field {
+            FormField::Sequence => self.sequence.push(c),
+            FormField::Description => self.description.push(c),
+        }
+    }
+
+    /// Whether nothing has been entered yet.
+    pub fn is_empty(&self) -> bool {
+        self.field == FormField::Sequence && self.sequence.is_empty()
+    }
+
+    /// Removes the last character of the active field.
+    /// Moves back to the sequence field if the description is already empty.
+    pub fn pop(&mut self) {
+        self.error = None;
+        match self.field {
+            FormField::Sequence => {
+                self.sequence.pop();
+            }
+            FormField::Description => {
+                if self.description.pop().is_none() {
+                    self.field = FormField::Sequence;
+                }
+            }
+        }
+    }
+
+    /// Moves on to the description field if the sequence is usable.
+    /// Returns true if the form is complete and can be saved.
+    pub fn submit(&mut self, mappings: &Mappings) -> bool {
+        match self.field {
+            FormField::Sequence => {
+                if matches!(self.sequence_status(mappings), SequenceStatus::Available) {
+                    self.field = FormField::Description;
+                }
+                false
+            }
+            FormField::Description => true,
+        }
+    }
+
+    pub fn sequence_status(&self, mappings: &Mappings) -> SequenceStatus {
+        if self.sequence.is_empty() {
+            return SequenceStatus::Empty;
+        }
+        match mappings.find_conflict(&self.sequence) {
+            Some(conflict) => SequenceStatus::Conflict(conflict.to_string()),
+            None => SequenceStatus::Available,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submit_requires_available_sequence() {
+        let mappings = Mappings::default();
+        let mut form = SaveForm::new("git push".into());
+
+        assert!(!form.submit(&mappings));
+        assert_eq!(form.field, FormField::Sequence);
+
+        // "g" is a prefix of existing git mappings
+        form.push('g');
+        assert!(!form.submit(&mappings));
+        assert_eq!(form.field, FormField::Sequence);
+
+        form.push('p');
+        assert!(!form.submit(&mappings));
+        assert_eq!(form.field, FormField::Description);
+
+        form.push('P');
+        assert_eq!(form.description, "P");
+        assert!(form.submit(&mappings));
+    }
+
+    #[test]
+    fn test_pop_returns_to_sequence() {
+        let mut form = SaveForm::new("git push".into());
+        form.push('x');
+        form.field = FormField::Description;
+        form.pop();
+        assert_eq!(form.field, FormField::Sequence);
+        assert!(!form.is_empty());
+        form.pop();
+        assert!(form.sequence.is_empty());
+        assert!(form.is_empty());
+    }
+}
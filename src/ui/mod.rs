@@ -1,8 +1,15 @@
+pub mod color;
+pub mod form;
 pub mod panel;
+pub mod preview;
 pub mod prompt;
 pub mod symbols;
 pub mod table;
 pub mod theme;
 
 mod area;
+mod buffer;
+mod command_preview;
 mod entry;
+mod footer;
+mod text;
@@ -1,15 +1,29 @@
 use std::{io::Write, time::Duration};
 
 use color_eyre::eyre::Result;
-use crossterm::{QueueableCommand, cursor, style::Stylize, terminal};
+use crossterm::{
+    QueueableCommand, cursor,
+    event::{KeyCode, KeyEvent},
+    terminal,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     Mappings, Symbols, Theme,
     cursor::query_cursor_position,
+    keybinding::{format_key_event, format_keysequence},
+    mappings::{MatchType, SortOrder},
     ui::{
         area::{Area, ColumnLayout},
+        buffer::Buffer,
+        color::ColorMode,
+        command_preview::{CommandPreview, PreviewConfig},
         entry::Entry,
+        footer::{self, FooterConfig, FooterValues},
+        form::{FormField, SaveForm, SequenceStatus},
+        text,
+        theme::{Role, ThemeFile},
     },
 };
 
@@ -28,7 +42,7 @@ where
     Ok(Duration::from_millis(ms))
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum BorderType {
     Rounded,
     Square,
@@ -36,17 +50,69 @@ pub enum BorderType {
     None,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Where the panel is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    /// At the bottom of the terminal.
+    #[default]
+    Bottom,
+    /// At the top of the terminal, or at the bottom if that would hide the prompt.
+    Top,
+    /// Directly below the prompt.
+    BelowCursor,
+    /// Directly above the prompt, or below it if there is not enough room above.
+    AboveCursor,
+    /// In the bottom right corner, one column wide unless `max_width` is set.
+    /// Like `bottom`, it still takes up and clears whole lines, so text left of it is
+    /// hidden while the panel is shown.
+    Right,
+}
+
+/// Horizontal alignment of a panel narrower than the terminal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(rename = "PanelConfig")]
 pub struct Config {
+    /// Whether to show the panel at all.
     pub enabled: bool,
+    /// Time in milliseconds before the panel pops up.
     #[serde(
         rename = "delay_ms",
         serialize_with = "duration_as_milliseconds",
         deserialize_with = "duration_from_milliseconds"
     )]
+    #[schemars(with = "u64")]
     pub delay: Duration,
+    /// Whether to silently skip the panel if it cannot be drawn instead of aborting.
     pub fail_silently: bool,
+    /// Name of a builtin theme or of a theme file in the `themes/` directory.
     pub theme_name: String,
+    /// Colors overriding those of `theme_name`, or of the theme given by `extends`.
+    #[serde(skip_serializing_if = "ThemeFile::is_empty")]
+    pub theme: ThemeFile,
+    /// Whether to render in color. `auto` disables colors if `NO_COLOR` is set or `TERM` is `dumb`.
+    pub color: ColorMode,
+    /// Order of the entries.
+    pub sort: SortOrder,
+    /// Whether to leave an empty line between prefixes and mappings.
+    /// Only has an effect with `sort = "prefixes_first"` or `"leaves_first"`.
+    pub separate_groups: bool,
+    /// Preview of the full command of the highlighted mapping,
+    /// or once the typed sequence leads to a single mapping.
+    pub preview: PreviewConfig,
+    /// Whether to capture the mouse while the panel is shown. Clicking an entry selects it,
+    /// scrolling flips pages and clicking outside the panel cancels.
+    pub mouse: bool,
     pub layout: LayoutConfig,
 }
 
@@ -57,18 +123,105 @@ impl std::default::Default for Config {
             delay: Duration::from_millis(500),
             fail_silently: true,
             theme_name: "catppuccin-mocha".into(),
+            theme: ThemeFile::default(),
+            color: ColorMode::Auto,
+            sort: SortOrder::Alphabetical,
+            separate_groups: false,
+            preview: PreviewConfig::default(),
+            mouse: true,
             layout: LayoutConfig::default(),
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Height of the panel in lines, either fixed or fitted to the entries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Height {
+    Auto,
+    Fixed(u16),
+}
+
+impl Serialize for Height {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Height::Auto => s.serialize_str("auto"),
+            Height::Fixed(height) => s.serialize_u16(*height),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Height {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct HeightVisitor;
+
+        impl serde::de::Visitor<'_> for HeightVisitor {
+            type Value = Height;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a number of lines or \"auto\"")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Height, E> {
+                match value {
+                    "auto" => Ok(Height::Auto),
+                    _ => Err(E::invalid_value(serde::de::Unexpected::Str(value), &self)),
+                }
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Height, E> {
+                u16::try_from(value)
+                    .map(Height::Fixed)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(value), &self))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Height, E> {
+                u16::try_from(value)
+                    .map(Height::Fixed)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(value), &self))
+            }
+        }
+
+        d.deserialize_any(HeightVisitor)
+    }
+}
+
+impl JsonSchema for Height {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Height".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "anyOf": [
+                { "type": "integer", "minimum": 0, "maximum": 65535 },
+                { "const": "auto" }
+            ]
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct LayoutConfig {
     pub border_type: BorderType,
     pub columns: ColumnLayout,
-    pub height: u16,
+    /// Height of the panel in lines, including border and footer.
+    /// `"auto"` fits the panel to the entries, within `min_height` and `max_height`.
+    pub height: Height,
+    /// Minimum height of the panel if `height` is `"auto"`.
+    pub min_height: u16,
+    /// Maximum height of the panel if `height` is `"auto"`.
+    pub max_height: u16,
+    /// Horizontal distance between the panel and the edges of the terminal.
     pub padding: u16,
+    pub position: Position,
+    /// Maximum width of the panel in characters, including the border.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u16>,
+    /// Alignment of the panel if it is narrower than the terminal. Ignored for `position = "right"`.
+    pub align: Align,
     pub symbols: Symbols,
+    pub footer: FooterConfig,
 }
 
 impl Default for LayoutConfig {
@@ -76,9 +229,121 @@ impl Default for LayoutConfig {
         Self {
             border_type: BorderType::Rounded,
             columns: ColumnLayout::default(),
-            height: 10,
+            height: Height::Fixed(10),
+            min_height: 5,
+            max_height: 20,
             padding: 2,
+            position: Position::Bottom,
+            max_width: None,
+            align: Align::Center,
             symbols: Symbols::default(),
+            footer: FooterConfig::default(),
+        }
+    }
+}
+
+/// Stands in for the empty line between prefixes and mappings. No key is empty.
+const SEPARATOR: &str = "";
+/// Lines needed by the save form: three fields, a blank line and an error.
+const FORM_HEIGHT: u16 = 5;
+
+/// Where a panel ends up on the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    area: Area,
+    /// Lines the screen has to be scrolled up by to make room below the prompt.
+    scroll_up: u16,
+    /// Whether the panel covers lines above the prompt, which can't be cleared afterwards
+    /// and are preserved by drawing on the alternate screen instead.
+    covers_history: bool,
+}
+
+impl Placement {
+    /// Places a panel of `height` lines in a terminal of the given size
+    /// with the cursor on line `cursor_y`. Without `alternate_screen`, panels that would cover
+    /// the history go below the prompt instead.
+    fn new(
+        layout: &LayoutConfig,
+        height: u16,
+        (cols, rows): (u16, u16),
+        cursor_y: u16,
+        alternate_screen: bool,
+    ) -> Self {
+        let height = height.min(rows);
+        let (x, width) = Self::horizontal(layout, cols);
+
+        let lines_above = cursor_y;
+        let lines_below = rows.saturating_sub(cursor_y + 1);
+        let room_above = alternate_screen && height <= lines_above;
+        let (y, scroll_up, covers_history) = match layout.position {
+            Position::Top if room_above => (0, 0, true),
+            Position::AboveCursor if room_above => (cursor_y - height, 0, true),
+            Position::BelowCursor | Position::AboveCursor => {
+                let scroll_up = height.saturating_sub(lines_below);
+                (cursor_y + 1 - scroll_up, scroll_up, false)
+            }
+            Position::Bottom | Position::Top | Position::Right => {
+                let scroll_up = height.saturating_sub(lines_below);
+                (rows - height, scroll_up, false)
+            }
+        };
+
+        Self {
+            area: Area {
+                x,
+                y,
+                width,
+                height,
+            },
+            scroll_up,
+            covers_history,
+        }
+    }
+
+    /// Computes the x position and width of a panel, which don't depend on its height.
+    fn horizontal(layout: &LayoutConfig, cols: u16) -> (u16, u16) {
+        let available = cols.saturating_sub(2 * layout.padding);
+        let default_width = match layout.position {
+            Position::Right => layout.columns.width + 4,
+            _ => available,
+        };
+        let width = layout.max_width.unwrap_or(default_width).min(available);
+        let free = available - width;
+        let offset = match (layout.position, layout.align) {
+            (Position::Right, _) | (_, Align::Right) => free,
+            (_, Align::Center) => free / 2,
+            (_, Align::Left) => 0,
+        };
+        (layout.padding + offset, width)
+    }
+}
+
+impl LayoutConfig {
+    /// Returns the height of a panel that has to show `num_keys` entries plus `extra_rows`
+    /// lines in a terminal `cols` characters wide.
+    fn height_for_entries(&self, num_keys: usize, extra_rows: u16, cols: u16) -> u16 {
+        let (_, width) = Placement::horizontal(self, cols);
+        let entry_width = width.saturating_sub(2);
+        let num_columns = ((entry_width + self.columns.spacing)
+            / (self.columns.width + self.columns.spacing).max(1))
+        .max(1);
+        let rows = u16::try_from(num_keys.div_ceil(num_columns as usize)).unwrap_or(u16::MAX);
+        self.height_for_rows(rows.saturating_add(extra_rows))
+    }
+
+    /// Returns the number of lines taken up by the border and footer in addition to the entries.
+    fn chrome_height(&self) -> u16 {
+        if self.footer.enabled { 4 } else { 2 }
+    }
+
+    /// Returns the height of a panel whose content takes up `rows` lines.
+    fn height_for_rows(&self, rows: u16) -> u16 {
+        match self.height {
+            Height::Fixed(height) => height,
+            Height::Auto => rows
+                .saturating_add(self.chrome_height())
+                .min(self.max_height)
+                .max(self.min_height),
         }
     }
 }
@@ -86,120 +351,620 @@ impl Default for LayoutConfig {
 pub struct Panel {
     pub config: Config,
     pub theme: Theme,
-    scroll_up: u16,
+    /// Line of the cursor when the panel was created, before any scrolling.
+    cursor_y: u16,
+    /// Where the panel is currently shown, if it has been drawn yet.
+    placement: Option<Placement>,
+    /// Whether the panel may cover the history by switching to the alternate screen,
+    /// which is only possible if the prompt line can be redrawn there.
+    alternate_screen: bool,
+    /// Whether the panel switched to the alternate screen since the last call to
+    /// [`Panel::take_prompt_redraw`].
+    prompt_redraw: bool,
+    /// Page of continuations currently shown, starting at 0.
+    page: usize,
+    /// Number of pages the continuations were split into on the last draw.
+    num_pages: usize,
+    /// Sequence the page belongs to. Typing another key starts over at the first page.
+    paged_sequence: String,
+    /// Index of the highlighted entry within `page_keys`.
+    highlight: Option<usize>,
+    /// Keys shown on the current page, column after column, as of the last draw.
+    page_keys: Vec<String>,
+    /// Number of entries per column as of the last draw.
+    column_height: usize,
+    /// Where the columns of entries were drawn on the last draw.
+    columns: Vec<Area>,
+    /// The terminal the panel is drawn on.
+    tty: std::fs::File,
+    /// What was drawn last at the current placement, so only changes have to be written.
+    frame: Option<Buffer>,
+    /// Keys closing the panel and going back a key, as shown in the footer.
+    cancel_key: KeyEvent,
+    back_key: KeyEvent,
+}
+
+/// Direction to move the highlight in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Returns the entry the highlight moves to from `current` in columns of `column_height`
+/// entries. The highlight stays put at the edges and skips separators.
+fn moved_highlight(
+    current: Option<usize>,
+    direction: Direction,
+    column_height: usize,
+    keys: &[String],
+) -> Option<usize> {
+    let is_entry = |index: &usize| keys.get(*index).is_some_and(|key| key != SEPARATOR);
+    let Some(current) = current.filter(is_entry) else {
+        return (0..keys.len()).find(is_entry);
+    };
+
+    let (target, forward) = match direction {
+        Direction::Up => (current.checked_sub(1), false),
+        Direction::Down => (Some(current + 1), true),
+        Direction::Left => (current.checked_sub(column_height), false),
+        // Moving into a shorter last column ends up on its last entry.
+        Direction::Right if (current / column_height + 1) * column_height < keys.len() => {
+            (Some((current + column_height).min(keys.len() - 1)), true)
+        }
+        Direction::Right => (None, true),
+    };
+    let Some(target) = target.filter(|target| *target < keys.len()) else {
+        return Some(current);
+    };
+    let mut candidate = Some(target);
+    while let Some(index) = candidate.filter(|index| *index < keys.len()) {
+        if is_entry(&index) {
+            return Some(index);
+        }
+        candidate = if forward {
+            Some(index + 1)
+        } else {
+            index.checked_sub(1)
+        };
+    }
+    Some(current)
+}
+
+/// Returns the typed keys, each with the icon of its group if it has one, e.g. "󰊢 g › s".
+fn breadcrumb(sequence: &str, mappings: &Mappings, separator: &str) -> String {
+    let mut crumbs = Vec::new();
+    for (end, c) in sequence.char_indices() {
+        let key = format_keysequence(&c.to_string());
+        let prefix = &sequence[..end + c.len_utf8()];
+        let icon = match mappings.match_partial_sequence(prefix) {
+            MatchType::Prefix {
+                group: Some(group), ..
+            } => group.icon.as_deref(),
+            _ => None,
+        };
+        crumbs.push(match icon {
+            Some(icon) => format!("{icon} {key}"),
+            None => key,
+        });
+    }
+    crumbs.join(&format!(" {separator} "))
+}
+
+/// Returns the page to show, clamped to the available pages, and the number of pages
+/// needed to show `num_keys` continuations with `per_page` of them on each page.
+fn clamp_page(num_keys: usize, per_page: usize, page: usize) -> (usize, usize) {
+    if per_page == 0 {
+        return (0, 1);
+    }
+    let num_pages = num_keys.div_ceil(per_page).max(1);
+    (page.min(num_pages - 1), num_pages)
 }
 
 impl Panel {
-    pub fn try_new(config: Config, theme: Theme) -> Result<Self> {
-        let mut tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
+    /// Creates a panel below the cursor. `alternate_screen` tells whether the prompt line can
+    /// be redrawn, which panels covering the history need, see [`Panel::take_prompt_redraw`].
+    pub fn try_new(config: Config, theme: Theme, alternate_screen: bool) -> Result<Self> {
+        let (_cursor_x, cursor_y) = query_cursor_position()?;
+        let tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
+        Ok(Self::new(config, theme, tty, cursor_y, alternate_screen))
+    }
+
+    /// Creates a panel that is only rendered with [`Panel::render_offscreen`], never drawn.
+    pub fn offscreen(config: Config, theme: Theme) -> std::io::Result<Self> {
+        let tty = std::fs::File::open("/dev/null")?;
+        Ok(Self::new(config, theme, tty, 0, false))
+    }
+
+    /// Renders the panel for `sequence` as it would look in a terminal of the given size,
+    /// without drawing it anywhere, e.g. to preview a theme.
+    pub fn render_offscreen(
+        &mut self,
+        sequence: &str,
+        mappings: &Mappings,
+        size: (u16, u16),
+    ) -> Buffer {
+        let (keys, preview, height) = self.layout(sequence, mappings, size.0);
+        let placement = Placement::new(&self.config.layout, height, size, 0, false);
+        self.render(sequence, mappings, keys, preview.as_ref(), &placement)
+    }
+
+    fn new(
+        config: Config,
+        theme: Theme,
+        tty: std::fs::File,
+        cursor_y: u16,
+        alternate_screen: bool,
+    ) -> Self {
+        Self {
+            config,
+            theme,
+            cursor_y,
+            placement: None,
+            alternate_screen,
+            prompt_redraw: false,
+            page: 0,
+            num_pages: 1,
+            paged_sequence: String::new(),
+            highlight: None,
+            page_keys: Vec::new(),
+            column_height: 1,
+            columns: Vec::new(),
+            tty,
+            frame: None,
+            cancel_key: KeyCode::Esc.into(),
+            back_key: KeyCode::Backspace.into(),
+        }
+    }
+
+    /// Sets the keys closing the panel and going back a key, which the footer refers to.
+    pub fn with_keys(mut self, cancel_key: KeyEvent, back_key: KeyEvent) -> Self {
+        self.cancel_key = cancel_key;
+        self.back_key = back_key;
+        self
+    }
+
+    /// Returns whether the panel switched to the alternate screen, which starts out empty,
+    /// since the last call. The prompt line has to be redrawn on it to stay visible.
+    pub fn take_prompt_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.prompt_redraw)
+    }
+
+    /// Flips to the next or previous page of continuations, wrapping around at the ends.
+    /// The caller has to redraw the panel afterwards.
+    pub fn flip_page(&mut self, forward: bool) {
+        self.page = if forward {
+            (self.page + 1) % self.num_pages
+        } else {
+            (self.page + self.num_pages - 1) % self.num_pages
+        };
+        // Keep highlighting, starting over at the top of the new page.
+        self.highlight = self.highlight.map(|_| 0);
+    }
+
+    /// Moves the highlight across the entries of the current page, starting at the first entry.
+    /// The caller has to redraw the panel afterwards.
+    pub fn move_highlight(&mut self, direction: Direction) {
+        self.highlight = moved_highlight(
+            self.highlight,
+            direction,
+            self.column_height,
+            &self.page_keys,
+        );
+    }
+
+    /// Returns the key of the highlighted entry, if any.
+    pub fn highlighted_key(&self) -> Option<&str> {
+        self.highlight
+            .and_then(|highlight| self.page_keys.get(highlight))
+            .map(String::as_str)
+    }
+
+    /// Returns the key of the entry drawn at the given cell, if any.
+    pub fn key_at(&self, x: u16, y: u16) -> Option<&str> {
+        let (i, column) = self
+            .columns
+            .iter()
+            .enumerate()
+            .find(|(_, column)| column.contains(x, y))?;
+        let index = i * self.column_height + (y - column.y) as usize;
+        self.page_keys
+            .get(index)
+            .map(String::as_str)
+            .filter(|key| *key != SEPARATOR)
+    }
+
+    /// Returns whether the given cell is covered by the panel.
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        self.placement
+            .is_some_and(|placement| placement.area.contains(x, y))
+    }
 
-        let (_cols, rows) = terminal::size()?;
+    /// Removes the panel as it was drawn and forgets where it was, so the next draw places it
+    /// anew around the cursor. Needed after a resize, which can move the prompt line.
+    pub fn relocate(&mut self) -> Result<()> {
+        self.clear()?;
+        self.placement = None;
         let (_cursor_x, cursor_y) = query_cursor_position()?;
-        let line_below_cursor = cursor_y.saturating_add(1);
+        self.cursor_y = cursor_y;
+        Ok(())
+    }
 
-        let lines_below = rows.saturating_sub(line_below_cursor);
-        let scroll_up = config.layout.height.saturating_sub(lines_below);
+    /// Moves the panel to a placement for `height` lines, if it isn't there already.
+    fn place(&mut self, height: u16) -> std::io::Result<Placement> {
+        let placement = Placement::new(
+            &self.config.layout,
+            height,
+            terminal::size()?,
+            self.cursor_y,
+            self.alternate_screen,
+        );
+        let previous = self.placement;
+        if previous == Some(placement) {
+            return Ok(placement);
+        }
 
-        if scroll_up > 0 {
-            tty.queue(terminal::ScrollUp(scroll_up))?
-                .queue(cursor::MoveUp(scroll_up))?;
+        // Whatever was drawn before is gone or somewhere else now.
+        self.frame = None;
+        let mut tty = &self.tty;
+        match previous {
+            // Stay on the alternate screen and only blank out the old panel.
+            Some(previous) if previous.covers_history && placement.covers_history => {
+                for line in previous.area.y..previous.area.y + previous.area.height {
+                    tty.queue(cursor::MoveTo(0, line))?
+                        .queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+                }
+            }
+            Some(_) => self.clear()?,
+            None => {}
         }
 
+        if placement.covers_history && !previous.is_some_and(|p| p.covers_history) {
+            tty.queue(terminal::EnterAlternateScreen)?;
+            self.prompt_redraw = true;
+        }
+        if placement.scroll_up > 0 {
+            tty.queue(terminal::ScrollUp(placement.scroll_up))?
+                .queue(cursor::MoveUp(placement.scroll_up))?;
+        }
         tty.flush()?;
 
-        Ok(Self {
-            config,
-            theme,
-            scroll_up,
-        })
+        self.placement = Some(placement);
+        Ok(placement)
     }
 
+    /// Removes the panel, leaving the screen as it was before the panel was shown.
     pub fn clear(&self) -> std::io::Result<()> {
-        let mut stdout = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
-        let (_cols, rows) = terminal::size()?;
-        let start_y = rows.saturating_sub(self.config.layout.height);
+        let Some(placement) = self.placement else {
+            return Ok(());
+        };
+        let mut stdout = &self.tty;
+
+        if placement.covers_history {
+            stdout.queue(terminal::LeaveAlternateScreen)?;
+            return stdout.flush();
+        }
 
+        // Everything below the prompt was empty before, so clearing it restores the screen.
         stdout
             .queue(cursor::SavePosition)?
-            .queue(cursor::MoveTo(0, start_y))?
+            .queue(cursor::MoveTo(0, placement.area.y))?
             .queue(terminal::Clear(terminal::ClearType::FromCursorDown))?
             .queue(cursor::RestorePosition)?;
 
-        if self.scroll_up > 0 {
+        if placement.scroll_up > 0 {
             stdout
-                .queue(terminal::ScrollDown(self.scroll_up))?
-                .queue(cursor::MoveDown(self.scroll_up))?;
+                .queue(terminal::ScrollDown(placement.scroll_up))?
+                .queue(cursor::MoveDown(placement.scroll_up))?;
         }
         stdout.flush()
     }
 
-    pub fn draw(&self, sequence: &str, mappings: &Mappings) -> Result<()> {
-        let mut tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
-        let (cols, rows) = terminal::size()?;
-        let start_y = rows.saturating_sub(self.config.layout.height);
-
-        let outer_area = Area {
-            x: self.config.layout.padding,
-            y: start_y,
-            width: cols.saturating_sub(2 * self.config.layout.padding),
-            height: self.config.layout.height,
-        };
-
-        tty.queue(cursor::SavePosition)?;
-        self.draw_border(&mut tty, &outer_area)?;
+    /// Computes the outer, entry and footer areas of a panel at the given placement.
+    fn areas(&self, placement: &Placement) -> (Area, Area, Area) {
         let border_width = 1;
-        let footer_height = 2;
+        let footer_height = if self.config.layout.footer.enabled {
+            2
+        } else {
+            0
+        };
 
-        let next_possible_keys = mappings.next_possible_keys(sequence);
+        let outer_area = placement.area;
         let entry_area = Area {
             x: outer_area.x + 1,
             y: outer_area.y + 1,
             width: outer_area.width.saturating_sub(2 * border_width),
             height: outer_area
                 .height
-                .saturating_sub(2 * border_width + footer_height),
+                .saturating_sub(self.config.layout.chrome_height()),
+        };
+        let footer_area = Area {
+            x: outer_area.x + 1,
+            y: (outer_area.y + outer_area.height).saturating_sub(footer_height),
+            width: outer_area.width.saturating_sub(2 * border_width),
+            height: footer_height,
         };
 
+        (outer_area, entry_area, footer_area)
+    }
+
+    pub fn draw(&mut self, sequence: &str, mappings: &Mappings) -> Result<()> {
+        if sequence != self.paged_sequence {
+            self.page = 0;
+            self.highlight = None;
+            self.paged_sequence = sequence.to_string();
+        }
+
+        let (cols, _rows) = terminal::size()?;
+        let (keys, preview, height) = self.layout(sequence, mappings, cols);
+        let placement = self.place(height)?;
+        let frame = self.render(sequence, mappings, keys, preview.as_ref(), &placement);
+        self.present(frame)?;
+
+        Ok(())
+    }
+
+    /// Returns the keys to show, the preview and the height the panel needs for both.
+    fn layout(
+        &self,
+        sequence: &str,
+        mappings: &Mappings,
+        cols: u16,
+    ) -> (Vec<String>, Option<CommandPreview>, u16) {
+        let mut next_possible_keys = mappings.next_possible_keys(sequence, self.config.sort);
+        if self.config.separate_groups && self.config.sort.splits_groups() {
+            let is_prefix = |key: &String| {
+                matches!(
+                    mappings.match_partial_sequence(&format!("{sequence}{key}")),
+                    MatchType::Prefix { .. }
+                )
+            };
+            if let Some(first) = next_possible_keys.first().map(is_prefix)
+                && let Some(boundary) = next_possible_keys
+                    .iter()
+                    .position(|k| is_prefix(k) != first)
+            {
+                next_possible_keys.insert(boundary, SEPARATOR.to_string());
+            }
+        }
+        let preview = self.preview(sequence, mappings, cols);
+        let preview_height = preview.as_ref().map_or(0, CommandPreview::height);
+        let height =
+            self.config
+                .layout
+                .height_for_entries(next_possible_keys.len(), preview_height, cols);
+        (next_possible_keys, preview, height)
+    }
+
+    /// Draws the panel into a frame at `placement`, remembering the page and where the entries went.
+    fn render(
+        &mut self,
+        sequence: &str,
+        mappings: &Mappings,
+        next_possible_keys: Vec<String>,
+        preview: Option<&CommandPreview>,
+        placement: &Placement,
+    ) -> Buffer {
+        let mut frame = Buffer::empty(placement.area);
+        let preview_height = preview.map_or(0, CommandPreview::height);
+        let (outer_area, mut entry_area, footer_area) = self.areas(placement);
+        entry_area.height = entry_area.height.saturating_sub(preview_height);
+        let preview_area = Area {
+            y: entry_area.y + entry_area.height,
+            height: preview_height,
+            ..entry_area
+        };
+
+        self.draw_border(&mut frame, &outer_area);
+
+        let max_columns = entry_area.split_horizontally(&self.config.layout.columns, &u16::MAX);
+        let per_page = max_columns.len() * entry_area.height as usize;
+        let num_keys = next_possible_keys.len();
+        let (page, num_pages) = clamp_page(num_keys, per_page, self.page);
+        self.page = page;
+        self.num_pages = num_pages;
+
+        let page_keys: Vec<String> = next_possible_keys
+            .into_iter()
+            .skip(page * per_page)
+            .take(per_page)
+            .collect();
+        let column_height = (entry_area.height as usize).max(1);
+        self.highlight = self
+            .highlight
+            .map(|highlight| highlight.min(page_keys.len().saturating_sub(1)));
+        let highlighted_key = self
+            .highlight
+            .and_then(|highlight| page_keys.get(highlight))
+            .cloned();
         let required_num_columns =
-            (next_possible_keys.len() as f64 / entry_area.height as f64).ceil() as u16;
+            u16::try_from(page_keys.len().div_ceil(column_height)).unwrap_or(u16::MAX);
         let columns =
             entry_area.split_horizontally(&self.config.layout.columns, &required_num_columns);
         for (i, column) in columns.iter().enumerate() {
-            let column_keys = next_possible_keys
+            let column_keys = page_keys
                 .iter()
                 .skip(i * column.height as usize)
                 .take(column.height as usize)
                 .cloned()
                 .collect::<Vec<_>>();
-            self.draw_entries(&mut tty, column, mappings, sequence, &column_keys)?;
+            self.draw_entries(
+                &mut frame,
+                column,
+                mappings,
+                sequence,
+                &column_keys,
+                highlighted_key.as_deref(),
+            );
         }
+        self.page_keys = page_keys;
+        self.column_height = column_height;
+        self.columns = columns;
 
-        let footer_area = Area {
-            x: outer_area.x + 1,
-            y: outer_area.y + outer_area.height - footer_height,
-            width: outer_area.width.saturating_sub(2 * border_width),
-            height: footer_height,
+        if let Some(preview) = preview {
+            preview.render(&mut frame, &preview_area);
+        }
+
+        // Never hide continuations without saying so.
+        let status = if per_page == 0 && num_keys > 0 {
+            let num_hidden = mappings
+                .next_possible_keys(sequence, self.config.sort)
+                .len();
+            format!("{num_hidden} keys hidden")
+        } else if num_pages > 1 {
+            format!("page {}/{}", page + 1, num_pages)
+        } else {
+            String::new()
+        };
+        let matches = match mappings.match_partial_sequence(sequence) {
+            MatchType::Prefix { count, .. } => count,
+            MatchType::Exact(_) => 1,
+            MatchType::None => 0,
+        };
+        let values = FooterValues {
+            sequence_begin: &self.config.layout.symbols.sequence_begin,
+            sequence: format_keysequence(sequence),
+            breadcrumb: breadcrumb(sequence, mappings, &self.config.layout.symbols.separator),
+            matches,
+            page: status,
+            cancel_key: format_key_event(&self.cancel_key),
+            back_key: format_key_event(&self.back_key),
         };
-        self.draw_footer(&mut tty, &footer_area, sequence)?;
-        tty.queue(cursor::RestorePosition)?;
+        let footer = &self.config.layout.footer;
+        self.draw_footer(
+            &mut frame,
+            &footer_area,
+            [&footer.left, &footer.center, &footer.right].map(|t| footer::render(t, &values)),
+        );
 
+        frame
+    }
+
+    /// Writes the parts of `frame` that changed since the last one.
+    fn present(&mut self, frame: Buffer) -> std::io::Result<()> {
+        frame.draw(&mut &self.tty, self.frame.as_ref())?;
+        self.frame = Some(frame);
         Ok(())
     }
 
-    fn draw_border(&self, tty: &mut std::fs::File, area: &Area) -> std::io::Result<()> {
-        let (top_left, top_right, bottom_left, bottom_right, horizontal, vertical) =
-            match self.config.layout.border_type {
-                BorderType::Rounded => ('╭', '╮', '╰', '╯', '─', '│'),
-                BorderType::Square => ('┌', '┐', '└', '┘', '─', '│'),
-                BorderType::Top => ('─', '─', ' ', ' ', '─', ' '),
-                BorderType::None => (' ', ' ', ' ', ' ', ' ', ' '),
+    /// Builds the preview of the mapping `sequence` leads to, if enabled and there is just one.
+    fn preview(&self, sequence: &str, mappings: &Mappings, cols: u16) -> Option<CommandPreview> {
+        if !self.config.preview.enabled {
+            return None;
+        }
+        let highlighted = self.highlighted_key().map(|key| format!("{sequence}{key}"));
+        let (key, mapping) = match highlighted
+            .as_deref()
+            .and_then(|key| Some((key, mappings.match_sequence(key)?)))
+        {
+            Some(highlighted) => highlighted,
+            None => mappings.unique_match(sequence)?,
+        };
+        let (_, width) = Placement::horizontal(&self.config.layout, cols);
+        Some(CommandPreview::new(
+            key,
+            mapping,
+            width.saturating_sub(2),
+            &self.config.preview,
+            &self.config.layout.symbols,
+            &self.theme,
+        ))
+    }
+
+    /// Draws the form for saving the current command line as a new mapping.
+    pub fn draw_form(&mut self, form: &SaveForm, mappings: &Mappings) -> Result<()> {
+        let placement = self.place(self.config.layout.height_for_rows(FORM_HEIGHT))?;
+        // The form has no entries to click.
+        self.columns.clear();
+        let frame = self.render_form(form, mappings, &placement);
+        self.present(frame)?;
+
+        Ok(())
+    }
+
+    /// Draws the save form into a frame at `placement`.
+    fn render_form(&self, form: &SaveForm, mappings: &Mappings, placement: &Placement) -> Buffer {
+        let mut frame = Buffer::empty(placement.area);
+        let (outer_area, entry_area, footer_area) = self.areas(placement);
+        self.draw_border(&mut frame, &outer_area);
+
+        let (status, status_role) = match form.sequence_status(mappings) {
+            SequenceStatus::Empty => ("type a key sequence".to_string(), Role::Muted),
+            SequenceStatus::Conflict(other) => (format!("conflicts with '{other}'"), Role::Warning),
+            SequenceStatus::Available => ("available".to_string(), Role::Success),
+        };
+
+        let lines = [
+            (false, "Command", form.command.as_str(), None),
+            (
+                form.field == FormField::Sequence,
+                "Sequence",
+                form.sequence.as_str(),
+                Some((status, status_role)),
+            ),
+            (
+                form.field == FormField::Description,
+                "Description",
+                form.description.as_str(),
+                None,
+            ),
+        ];
+
+        let label_width = 12;
+        let num_lines = lines.len() as u16;
+        for (line, (active, label, value, status)) in (entry_area.y..).zip(lines) {
+            if line >= entry_area.y + entry_area.height {
+                break;
+            }
+            let marker = if active {
+                self.config.layout.symbols.sequence_begin.as_str()
+            } else {
+                " "
             };
+            let label = format!("{marker} {label:<label_width$}");
+            let available_width = (entry_area.width as usize).saturating_sub(text::width(&label));
+            let value = text::truncate(value, available_width);
+            let x = frame.set_styled(entry_area.x, line, &self.theme.style(label, Role::Muted));
+            let x = frame.set_styled(x, line, &self.theme.style(value, Role::Sequence));
+
+            if let Some((status, role)) = status {
+                let remaining = available_width.saturating_sub(text::width(value) + 2);
+                let status = text::truncate(&status, remaining);
+                frame.set_styled(x + 2, line, &self.theme.style(status, role));
+            }
+        }
+
+        if let Some(error) = &form.error {
+            let line = entry_area.y + num_lines + 1;
+            if line < entry_area.y + entry_area.height {
+                let error = text::truncate(error, entry_area.width as usize);
+                frame.set_styled(entry_area.x, line, &self.theme.style(error, Role::Warning));
+            }
+        }
+
+        let help = vec![
+            (format_key_event(&KeyCode::Enter.into()), Role::Key),
+            (" confirm  ".into(), Role::Text),
+            (format_key_event(&self.back_key), Role::Key),
+            (" back".into(), Role::Text),
+        ];
+        self.draw_footer(&mut frame, &footer_area, [Vec::new(), help, Vec::new()]);
+
+        frame
+    }
+
+    fn draw_border(&self, frame: &mut Buffer, area: &Area) {
+        let (top_left, top_right, bottom_left, bottom_right, horizontal, vertical) = self
+            .config
+            .layout
+            .symbols
+            .border(&self.config.layout.border_type);
 
         let inner_width = area.width.saturating_sub(2);
         let horizontal_line = horizontal.to_string().repeat(inner_width.into());
 
-        tty.queue(cursor::MoveTo(area.x, area.y))?;
-
         // Top border
         if !matches!(self.config.layout.border_type, BorderType::None) {
             let top = format!(
@@ -207,64 +972,63 @@ impl Panel {
                 line = horizontal_line,
                 tl = top_left,
                 tr = top_right,
-            )
-            .with(self.theme.accent.into())
-            .on(self.theme.background.into());
-            write!(tty, "{}", top)?;
+            );
+            frame.set_styled(area.x, area.y, &self.theme.style(top, Role::Border));
         }
 
+        let has_bottom = matches!(
+            self.config.layout.border_type,
+            BorderType::Rounded | BorderType::Square
+        );
+
         // Vertical sides
-        for i in 1..self.config.layout.height {
-            tty.queue(cursor::MoveTo(area.x, area.y + i))?;
+        let sides_end = if has_bottom {
+            area.height.saturating_sub(1)
+        } else {
+            area.height
+        };
+        for i in 1..sides_end {
             let line = format!(
                 "{vl}{space}{vr}",
                 space = " ".repeat(inner_width.into()),
                 vl = vertical,
                 vr = vertical
-            )
-            .with(self.theme.accent.into())
-            .on(self.theme.background.into());
-            write!(tty, "{}", line)?;
+            );
+            frame.set_styled(area.x, area.y + i, &self.theme.style(line, Role::Border));
         }
 
         // Bottom border
-        if matches!(
-            self.config.layout.border_type,
-            BorderType::Rounded | BorderType::Square
-        ) {
-            tty.queue(cursor::MoveTo(area.x, area.y + area.height))?;
+        if has_bottom {
             let bottom = format!(
                 "{bl}{line}{br}",
                 line = horizontal_line,
                 bl = bottom_left,
                 br = bottom_right
-            )
-            .with(self.theme.accent.into())
-            .on(self.theme.background.into());
-            write!(tty, "{}", bottom)?;
+            );
+            frame.set_styled(
+                area.x,
+                area.y + area.height.saturating_sub(1),
+                &self.theme.style(bottom, Role::Border),
+            );
         }
-
-        tty.flush()?;
-
-        Ok(())
     }
 
-    pub fn draw_entries(
+    fn draw_entries(
         &self,
-        tty: &mut std::fs::File,
+        frame: &mut Buffer,
         area: &Area,
         mappings: &Mappings,
         sequence: &str,
-        keys: &Vec<String>,
-    ) -> std::io::Result<()> {
-        let mut line = area.y;
-
-        for key in keys {
+        keys: &[String],
+        highlighted_key: Option<&str>,
+    ) {
+        for (line, key) in (area.y..).zip(keys.iter()) {
             if line >= area.y + area.height {
                 break; // stop if no more vertical space
             }
-            tty.queue(cursor::MoveTo(area.x, line))?;
-
+            if key == SEPARATOR {
+                continue;
+            }
             let full_sequence = format!("{sequence}{key}");
             let match_type = mappings.match_partial_sequence(&full_sequence);
 
@@ -274,46 +1038,47 @@ impl Panel {
                 area.width,
                 &self.config.layout.symbols,
                 &self.theme,
+                highlighted_key == Some(key.as_str()),
             );
-            stylized_entry.to_tty(tty)?;
-
-            line += 1;
+            stylized_entry.render(frame, area.x, line);
         }
-
-        Ok(())
     }
 
-    pub fn draw_footer(
+    /// Draws the left, centered and right parts of the footer, unless it is disabled.
+    fn draw_footer(
         &self,
-        tty: &mut std::fs::File,
+        frame: &mut Buffer,
         area: &Area,
-        sequence: &str,
-    ) -> std::io::Result<()> {
-        let help_text = "󱊷  close  󰁮  back";
-        let styled_help_text = help_text
-            .with(self.theme.text_primary.into())
-            .on(self.theme.background.into());
-        let center_x = area.x + (area.width.saturating_sub(help_text.chars().count() as u16)) / 2;
-        tty.queue(cursor::MoveTo(center_x, area.y))?;
-        write!(tty, "{}", styled_help_text)?;
-
-        tty.queue(cursor::MoveTo(area.x, area.y))?;
-        let arrow = self
-            .config
-            .layout
-            .symbols
-            .sequence_begin
-            .to_string()
-            .with(self.theme.text_secondary.into())
-            .on(self.theme.background.into());
-        write!(tty, "{}", arrow)?;
-        let sequence_text = sequence
-            .to_string()
-            .with(self.theme.text_primary.into())
-            .on(self.theme.background.into());
-        write!(tty, "{}", sequence_text)?;
+        [left, center, right]: [Vec<(String, Role)>; 3],
+    ) {
+        if !self.config.layout.footer.enabled {
+            return;
+        }
+        let width = |parts: &[(String, Role)]| -> u16 {
+            parts.iter().map(|(part, _)| text::width(part) as u16).sum()
+        };
 
-        Ok(())
+        // Drawn from the center outwards, so the sequence stays readable if the parts overlap.
+        let positions = [
+            (
+                area.x + area.width.saturating_sub(width(&center)) / 2,
+                center,
+            ),
+            (
+                (area.x + area.width).saturating_sub(width(&right) + 1),
+                right,
+            ),
+            (area.x, left),
+        ];
+        for (x, parts) in positions {
+            let mut x = x;
+            let mut room = (area.x + area.width).saturating_sub(x) as usize;
+            for (part, role) in parts {
+                let part = text::truncate(&part, room);
+                room -= text::width(part);
+                x = frame.set_styled(x, area.y, &self.theme.style(part, role));
+            }
+        }
     }
 }
 
@@ -322,3 +1087,310 @@ impl Drop for Panel {
         let _ = self.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::symbols::SymbolPreset;
+
+    const TERMINAL: (u16, u16) = (100, 40);
+
+    fn test_panel() -> Panel {
+        let mut config = Config::default();
+        config.layout.symbols = Symbols::preset(SymbolPreset::Ascii);
+        config.layout.height = Height::Fixed(7);
+        config.layout.columns = ColumnLayout {
+            width: 26,
+            spacing: 2,
+            centred: false,
+        };
+        // Nothing is written to the terminal when rendering.
+        let tty = std::fs::File::open("/dev/null").unwrap();
+        Panel::new(config, Theme::default().monochrome(), tty, 0, true)
+    }
+
+    /// Renders the panel for `sequence` in a terminal of 60 columns.
+    fn render(panel: &mut Panel, sequence: &str) -> Buffer {
+        let mappings = Mappings::default();
+        let (keys, preview, height) = panel.layout(sequence, &mappings, 60);
+        let placement = Placement::new(&panel.config.layout, height, (60, 20), 0, true);
+        panel.render(sequence, &mappings, keys, preview.as_ref(), &placement)
+    }
+
+    #[test]
+    fn test_render_entries() {
+        let mut panel = test_panel();
+        assert_eq!(
+            render(&mut panel, "").to_string(),
+            [
+                "+------------------------------------------------------+",
+                "|g -> +3 mappings            s -> +1 mappings          |",
+                "|i -> +1 mappings            y -> Append copy to~ a    |",
+                "|p -> +1 mappings                                      |",
+                "|                                                      |",
+                "|>               <Esc> close  <BS> back                |",
+                "+------------------------------------------------------+",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            render(&mut panel, "g").to_string(),
+            [
+                "+------------------------------------------------------+",
+                "|a -> Git add all     r   !                            |",
+                "|c -> Start a Git co~ r                                |",
+                "|s -> Git status      r   !                            |",
+                "|                                                      |",
+                "|>g              <Esc> close  <BS> back                |",
+                "+------------------------------------------------------+",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_highlight_and_preview() {
+        let mut panel = test_panel();
+        panel.config.layout.height = Height::Auto;
+        panel.config.preview.enabled = true;
+        render(&mut panel, "g");
+        panel.move_highlight(Direction::Down);
+
+        let frame = render(&mut panel, "g");
+        assert_eq!(
+            frame.to_string(),
+            [
+                "+------------------------------------------------------+",
+                "|a -> Git add all     r   !  s -> Git status      r   !|",
+                "|c -> Start a Git co~ r                                |",
+                "|------------------------------------------------------|",
+                "|git add .                                             |",
+                "|ga: replaces the prompt, executes it                  |",
+                "|                                                      |",
+                "|>g              <Esc> close  <BS> back                |",
+                "+------------------------------------------------------+",
+            ]
+            .join("\n")
+        );
+        // The panel is 9 lines high at the bottom, 2 columns from the left edge of the terminal.
+        let reversed = |x: u16, y: u16| {
+            let cell = frame.cell(2 + x, 11 + y).unwrap();
+            cell.style
+                .attributes
+                .has(crossterm::style::Attribute::Reverse)
+        };
+        assert!(reversed(1, 1) && reversed(26, 1));
+        assert!(!reversed(1, 2) && !reversed(29, 1));
+    }
+
+    #[test]
+    fn test_redraw_writes_only_changes() {
+        let mut panel = test_panel();
+        let previous = render(&mut panel, "g");
+        panel.move_highlight(Direction::Down);
+        let frame = render(&mut panel, "g");
+
+        let mut out = Vec::new();
+        frame.draw(&mut out, Some(&previous)).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Git add all"), "{out:?}");
+        assert!(
+            !out.contains("Git status") && !out.contains("---"),
+            "{out:?}"
+        );
+    }
+
+    #[test]
+    fn test_render_form() {
+        let mut panel = test_panel();
+        panel.config.layout.height = Height::Auto;
+        let height = panel.config.layout.height_for_rows(FORM_HEIGHT);
+        let placement = Placement::new(&panel.config.layout, height, (60, 20), 0, true);
+        let mut form = SaveForm::new("echo hi".into());
+        form.error = Some("Sequence must not be empty".into());
+        assert_eq!(
+            panel
+                .render_form(&form, &Mappings::default(), &placement)
+                .to_string(),
+            [
+                "+------------------------------------------------------+",
+                "|  Command     echo hi                                 |",
+                "|> Sequence      type a key sequence                   |",
+                "|  Description                                         |",
+                "|                                                      |",
+                "|Sequence must not be empty                            |",
+                "|                                                      |",
+                "|               <CR> confirm  <BS> back                |",
+                "+------------------------------------------------------+",
+            ]
+            .join("\n")
+        );
+    }
+
+    fn place(position: Position, cursor_y: u16) -> Placement {
+        let layout = LayoutConfig {
+            position,
+            ..Default::default()
+        };
+        Placement::new(&layout, 10, TERMINAL, cursor_y, true)
+    }
+
+    #[test]
+    fn test_bottom_scrolls_only_without_room() {
+        let placement = place(Position::Bottom, 5);
+        assert_eq!((placement.area.y, placement.scroll_up), (30, 0));
+        assert!(!placement.covers_history);
+
+        let placement = place(Position::Bottom, 35);
+        assert_eq!((placement.area.y, placement.scroll_up), (30, 6));
+    }
+
+    #[test]
+    fn test_below_cursor() {
+        let placement = place(Position::BelowCursor, 5);
+        assert_eq!((placement.area.y, placement.scroll_up), (6, 0));
+
+        let placement = place(Position::BelowCursor, 39);
+        assert_eq!((placement.area.y, placement.scroll_up), (30, 10));
+    }
+
+    #[test]
+    fn test_above_cursor_and_top_cover_history_if_there_is_room() {
+        let placement = place(Position::AboveCursor, 20);
+        assert_eq!((placement.area.y, placement.scroll_up), (10, 0));
+        assert!(placement.covers_history);
+
+        let placement = place(Position::Top, 20);
+        assert_eq!((placement.area.y, placement.scroll_up), (0, 0));
+        assert!(placement.covers_history);
+
+        // The panel must not hide the prompt, so it falls back to below the cursor.
+        let placement = place(Position::AboveCursor, 5);
+        assert_eq!((placement.area.y, placement.scroll_up), (6, 0));
+        assert!(!placement.covers_history);
+
+        let placement = place(Position::Top, 5);
+        assert_eq!((placement.area.y, placement.scroll_up), (30, 0));
+        assert!(!placement.covers_history);
+    }
+
+    #[test]
+    fn test_no_alternate_screen_without_prompt_redraw() {
+        let layout = |position| LayoutConfig {
+            position,
+            ..Default::default()
+        };
+        let placement = Placement::new(&layout(Position::AboveCursor), 10, TERMINAL, 20, false);
+        assert_eq!((placement.area.y, placement.scroll_up), (21, 0));
+        assert!(!placement.covers_history);
+
+        let placement = Placement::new(&layout(Position::Top), 10, TERMINAL, 20, false);
+        assert_eq!((placement.area.y, placement.scroll_up), (30, 0));
+        assert!(!placement.covers_history);
+    }
+
+    #[test]
+    fn test_width_and_alignment() {
+        let placement = place(Position::Bottom, 5);
+        assert_eq!((placement.area.x, placement.area.width), (2, 96));
+
+        let placement = place(Position::Right, 5);
+        assert_eq!((placement.area.x, placement.area.width), (54, 44));
+
+        let mut layout = LayoutConfig {
+            max_width: Some(50),
+            ..Default::default()
+        };
+        let placement = Placement::new(&layout, 10, TERMINAL, 5, true);
+        assert_eq!((placement.area.x, placement.area.width), (25, 50));
+
+        layout.align = Align::Left;
+        assert_eq!(Placement::new(&layout, 10, TERMINAL, 5, true).area.x, 2);
+        layout.align = Align::Right;
+        assert_eq!(Placement::new(&layout, 10, TERMINAL, 5, true).area.x, 48);
+
+        layout.max_width = Some(500);
+        assert_eq!(
+            Placement::new(&layout, 10, TERMINAL, 5, true).area.width,
+            96
+        );
+    }
+
+    #[test]
+    fn test_auto_height() {
+        let mut layout = LayoutConfig::default();
+        assert_eq!(layout.height_for_entries(3, 0, 100), 10);
+
+        layout.height = Height::Auto;
+        // Two columns of 40 fit into 100 - 2 * 2 padding - 2 border characters.
+        assert_eq!(
+            layout.height_for_entries(3, 0, 100),
+            2 + layout.chrome_height()
+        );
+        assert_eq!(
+            layout.height_for_entries(7, 0, 100),
+            4 + layout.chrome_height()
+        );
+        // Room for a preview below the entries.
+        assert_eq!(
+            layout.height_for_entries(3, 4, 100),
+            6 + layout.chrome_height()
+        );
+        assert_eq!(layout.height_for_entries(1, 0, 100), layout.min_height);
+        assert_eq!(layout.height_for_entries(100, 0, 100), layout.max_height);
+        // A narrow terminal fits a single column.
+        assert_eq!(
+            layout.height_for_entries(3, 0, 50),
+            3 + layout.chrome_height()
+        );
+    }
+
+    #[test]
+    fn test_move_highlight() {
+        // Two columns of three: a, b, c | (separator), d, e
+        let keys: Vec<String> = ["a", "b", "c", SEPARATOR, "d", "e"]
+            .map(String::from)
+            .to_vec();
+        let moved = |current, direction| moved_highlight(current, direction, 3, &keys);
+
+        assert_eq!(moved(None, Direction::Right), Some(0));
+        assert_eq!(moved(Some(0), Direction::Up), Some(0));
+        assert_eq!(moved(Some(0), Direction::Down), Some(1));
+        assert_eq!(moved(Some(2), Direction::Down), Some(4));
+        assert_eq!(moved(Some(4), Direction::Up), Some(2));
+        assert_eq!(moved(Some(5), Direction::Down), Some(5));
+        assert_eq!(moved(Some(1), Direction::Right), Some(4));
+        assert_eq!(moved(Some(0), Direction::Right), Some(4));
+        assert_eq!(moved(Some(4), Direction::Right), Some(4));
+        assert_eq!(moved(Some(5), Direction::Left), Some(2));
+        assert_eq!(moved(Some(1), Direction::Left), Some(1));
+
+        // A shorter last column is still reachable from the bottom of the first.
+        let keys: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        assert_eq!(
+            moved_highlight(Some(2), Direction::Right, 3, &keys),
+            Some(3)
+        );
+        assert_eq!(moved_highlight(Some(0), Direction::Down, 3, &[]), None);
+    }
+
+    #[test]
+    fn test_clamp_page() {
+        assert_eq!(clamp_page(0, 10, 0), (0, 1));
+        assert_eq!(clamp_page(10, 10, 0), (0, 1));
+        assert_eq!(clamp_page(25, 10, 2), (2, 3));
+        // The page shrinks back when fewer continuations are left.
+        assert_eq!(clamp_page(15, 10, 2), (1, 2));
+        // Nothing fits, so there is nothing to page through.
+        assert_eq!(clamp_page(15, 0, 3), (0, 1));
+    }
+
+    #[test]
+    fn test_parse_height() {
+        let parse = |value: &str| toml::from_str::<LayoutConfig>(&format!("height = {value}"));
+        assert_eq!(parse("\"auto\"").unwrap().height, Height::Auto);
+        assert_eq!(parse("12").unwrap().height, Height::Fixed(12));
+        assert!(parse("\"tall\"").is_err());
+        assert!(parse("-1").is_err());
+    }
+}
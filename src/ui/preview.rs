@@ -0,0 +1,88 @@
+use color_eyre::eyre::Result;
+
+use crate::{
+    Config, Mappings, Theme,
+    ui::panel::{Height, Panel, Position},
+};
+
+/// Size of the terminal the sample panel is rendered for.
+const SAMPLE_SIZE: (u16, u16) = (72, 20);
+
+/// Renders a sample panel with the default mappings in the given theme, laid out as
+/// configured in `config`.
+pub fn render_sample(config: &Config, theme: &Theme) -> Result<String> {
+    let (cancel_key, back_key) = config.cancel_and_back_key_events()?;
+    let mut config = config.panel.clone();
+    config.layout.height = Height::Auto;
+    config.layout.min_height = 0;
+    config.layout.max_height = SAMPLE_SIZE.1;
+    config.layout.padding = 0;
+    config.layout.position = Position::Bottom;
+    config.layout.max_width = None;
+
+    let frame = Panel::offscreen(config, theme.clone())?
+        .with_keys(cancel_key, back_key)
+        .render_offscreen("", &Mappings::default(), SAMPLE_SIZE);
+    let mut out = Vec::new();
+    frame.write_lines(&mut out)?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Symbols,
+        ui::{symbols::SymbolPreset, text},
+    };
+
+    fn stripped(sample: String) -> String {
+        String::from_utf8(strip_ansi_escapes::strip(sample)).unwrap()
+    }
+
+    #[test]
+    fn test_sample_lines_have_equal_width() {
+        for name in Theme::BUILTIN_NAMES {
+            let theme = Theme::builtin(name).unwrap();
+            let sample = stripped(render_sample(&Config::default(), &theme).unwrap());
+
+            let widths: Vec<usize> = sample.lines().map(text::width).collect();
+            assert!(widths.len() > 3, "{sample}");
+            assert!(widths.iter().all(|&width| width == widths[0]), "{sample}");
+        }
+    }
+
+    #[test]
+    fn test_ascii_sample() {
+        let mut config = Config::default();
+        config.panel.layout.symbols = Symbols::preset(SymbolPreset::Ascii);
+        let sample = stripped(render_sample(&config, &Theme::default()).unwrap());
+
+        assert!(sample.is_ascii(), "{sample}");
+        assert!(sample.starts_with('+'), "{sample}");
+    }
+
+    #[test]
+    fn test_sample_uses_footer_template() {
+        let mut config = Config::default();
+        config.panel.layout.footer.left = "sample footer".into();
+        let sample = stripped(render_sample(&config, &Theme::default()).unwrap());
+
+        assert!(sample.contains("sample footer"), "{sample}");
+
+        config.cancel_key = "<C-x>".into();
+        let sample = stripped(render_sample(&config, &Theme::default()).unwrap());
+        assert!(sample.contains("<C-x> close"), "{sample}");
+    }
+
+    #[test]
+    fn test_monochrome_sample_has_no_colors() {
+        let theme = Theme::default().monochrome();
+        let sample = render_sample(&Config::default(), &theme).unwrap();
+
+        assert!(!sample.contains("\x1b[38;"), "{sample:?}");
+        assert!(!sample.contains("\x1b[48;"), "{sample:?}");
+        // Keys are still distinguished from descriptions.
+        assert!(sample.contains("\x1b[1m"), "{sample:?}");
+    }
+}
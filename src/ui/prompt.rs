@@ -50,9 +50,13 @@ pub struct PromptGuard {
 
 impl PromptGuard {
     /// Create a new redraw guard.
-    pub fn try_new() -> Result<Self> {
+    /// If `monochrome` is set, colors and other styling are stripped from the prompt.
+    pub fn try_new(monochrome: bool) -> Result<Self> {
         let tty = OpenOptions::new().write(true).open("/dev/tty")?;
-        let prompt = env::var("LEADR_PROMPT")?;
+        let mut prompt = env::var("LEADR_PROMPT")?;
+        if monochrome {
+            prompt = String::from_utf8_lossy(&strip_ansi_escapes::strip(&prompt)).into_owned();
+        }
         let input = env::var("LEADR_CURRENT_INPUT")?;
 
         let (cursor_column, cursor_line) = query_cursor_position()?;
@@ -66,6 +70,13 @@ impl PromptGuard {
         })
     }
 
+    /// Look up the line of the prompt again, which the terminal may move when it is resized.
+    pub fn relocate(&mut self) -> Result<()> {
+        let (_cursor_column, cursor_line) = query_cursor_position()?;
+        self.cursor_line = cursor_line;
+        Ok(())
+    }
+
     /// Redraw the current prompt + input line, restoring cursor position.
     pub fn redraw(&mut self) -> Result<()> {
         let prompt_width = visible_width(&self.prompt) as u16;
@@ -1,30 +1,212 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use crate::ui::panel::BorderType;
+
+/// Set of symbols the others are taken from unless they are set individually.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolPreset {
+    /// Glyphs of a patched [Nerd Font](https://www.nerdfonts.com).
+    #[default]
+    Nerd,
+    /// Characters most fonts come with.
+    Unicode,
+    /// Plain ASCII, for fonts and terminals without anything else.
+    Ascii,
+}
+
+/// Symbols used to render the panel.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(from = "SymbolsFile", into = "SymbolsFile")]
 pub struct Symbols {
+    pub preset: SymbolPreset,
     pub append: String,
     pub arrow: String,
+    pub ellipsis: String,
     pub evaluate: String,
     pub execute: String,
     pub insert: String,
     pub prepend: String,
     pub replace: String,
+    pub separator: String,
     pub sequence_begin: String,
     pub surround: String,
 }
 
+impl Symbols {
+    pub fn preset(preset: SymbolPreset) -> Self {
+        let symbols = match preset {
+            SymbolPreset::Nerd => ["󰌒", "→", "…", "󰊕", "󰌑", "", "⇤", " ", "›", "󰄾", "󰅪"],
+            SymbolPreset::Unicode => ["⇥", "→", "…", "ƒ", "⏎", "‸", "⇤", "↺", "›", "»", "↔"],
+            SymbolPreset::Ascii => ["a", "->", "~", "$", "!", "i", "p", "r", ">", ">", "s"],
+        };
+        let [
+            append,
+            arrow,
+            ellipsis,
+            evaluate,
+            execute,
+            insert,
+            prepend,
+            replace,
+            separator,
+            sequence_begin,
+            surround,
+        ] = symbols.map(String::from);
+        Self {
+            preset,
+            append,
+            arrow,
+            ellipsis,
+            evaluate,
+            execute,
+            insert,
+            prepend,
+            replace,
+            separator,
+            sequence_begin,
+            surround,
+        }
+    }
+
+    /// Returns the characters a border of `border_type` is drawn with, as
+    /// top left, top right, bottom left and bottom right corner, horizontal and vertical line.
+    pub fn border(&self, border_type: &BorderType) -> (char, char, char, char, char, char) {
+        match (border_type, self.preset) {
+            (BorderType::None, _) => (' ', ' ', ' ', ' ', ' ', ' '),
+            (BorderType::Top, SymbolPreset::Ascii) => ('-', '-', ' ', ' ', '-', ' '),
+            (BorderType::Top, _) => ('─', '─', ' ', ' ', '─', ' '),
+            (_, SymbolPreset::Ascii) => ('+', '+', '+', '+', '-', '|'),
+            (BorderType::Rounded, _) => ('╭', '╮', '╰', '╯', '─', '│'),
+            (BorderType::Square, _) => ('┌', '┐', '└', '┘', '─', '│'),
+        }
+    }
+}
+
 impl std::default::Default for Symbols {
     fn default() -> Self {
+        Self::preset(SymbolPreset::default())
+    }
+}
+
+/// The `[panel.layout.symbols]` table, which only needs to set the symbols that differ from its preset.
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct SymbolsFile {
+    #[serde(default)]
+    preset: SymbolPreset,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    append: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arrow: Option<String>,
+    /// Marks text that was cut off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ellipsis: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evaluate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execute: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    insert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prepend: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replace: Option<String>,
+    /// Goes between the keys of the `{breadcrumb}` in the footer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequence_begin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surround: Option<String>,
+}
+
+impl From<Symbols> for SymbolsFile {
+    fn from(symbols: Symbols) -> Self {
+        // Symbols equal to the preset are left out, so changing the preset changes them as well.
+        let preset = Symbols::preset(symbols.preset);
+        let changed = |symbol: String, default: String| (symbol != default).then_some(symbol);
+        Self {
+            preset: symbols.preset,
+            append: changed(symbols.append, preset.append),
+            arrow: changed(symbols.arrow, preset.arrow),
+            ellipsis: changed(symbols.ellipsis, preset.ellipsis),
+            evaluate: changed(symbols.evaluate, preset.evaluate),
+            execute: changed(symbols.execute, preset.execute),
+            insert: changed(symbols.insert, preset.insert),
+            prepend: changed(symbols.prepend, preset.prepend),
+            replace: changed(symbols.replace, preset.replace),
+            separator: changed(symbols.separator, preset.separator),
+            sequence_begin: changed(symbols.sequence_begin, preset.sequence_begin),
+            surround: changed(symbols.surround, preset.surround),
+        }
+    }
+}
+
+impl From<SymbolsFile> for Symbols {
+    fn from(file: SymbolsFile) -> Self {
+        let preset = Symbols::preset(file.preset);
         Self {
-            append: "󰌒".into(),
-            arrow: "→".into(),
-            evaluate: "󰊕".into(),
-            execute: "󰌑".into(),
-            insert: "".into(),
-            prepend: "⇤".into(),
-            replace: " ".into(),
-            sequence_begin: "󰄾".into(),
-            surround: "󰅪".into(),
+            preset: file.preset,
+            append: file.append.unwrap_or(preset.append),
+            arrow: file.arrow.unwrap_or(preset.arrow),
+            ellipsis: file.ellipsis.unwrap_or(preset.ellipsis),
+            evaluate: file.evaluate.unwrap_or(preset.evaluate),
+            execute: file.execute.unwrap_or(preset.execute),
+            insert: file.insert.unwrap_or(preset.insert),
+            prepend: file.prepend.unwrap_or(preset.prepend),
+            replace: file.replace.unwrap_or(preset.replace),
+            separator: file.separator.unwrap_or(preset.separator),
+            sequence_begin: file.sequence_begin.unwrap_or(preset.sequence_begin),
+            surround: file.surround.unwrap_or(preset.surround),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_on_top_of_preset() {
+        let symbols: Symbols = toml::from_str("preset = \"ascii\"\narrow = \"=>\"").unwrap();
+        assert_eq!(symbols.arrow, "=>");
+        assert_eq!(symbols.sequence_begin, ">");
+        assert_eq!(symbols.border(&BorderType::Rounded).0, '+');
+
+        let symbols: Symbols = toml::from_str("arrow = \"=>\"").unwrap();
+        assert_eq!(symbols.preset, SymbolPreset::Nerd);
+        assert_eq!(symbols.sequence_begin, Symbols::default().sequence_begin);
+
+        // Only symbols differing from the preset are written, so another preset applies to the rest.
+        let serialized = toml::to_string(&symbols).unwrap();
+        assert_eq!(serialized, "preset = \"nerd\"\narrow = \"=>\"\n");
+        let symbols: Symbols = toml::from_str(&serialized.replace("nerd", "unicode")).unwrap();
+        assert_eq!(symbols.arrow, "=>");
+        assert_eq!(symbols.sequence_begin, "»");
+    }
+
+    #[test]
+    fn test_ascii_preset_is_ascii() {
+        let symbols = Symbols::preset(SymbolPreset::Ascii);
+        let symbols = [
+            &symbols.append,
+            &symbols.arrow,
+            &symbols.ellipsis,
+            &symbols.evaluate,
+            &symbols.execute,
+            &symbols.insert,
+            &symbols.prepend,
+            &symbols.replace,
+            &symbols.separator,
+            &symbols.sequence_begin,
+            &symbols.surround,
+        ];
+        assert!(symbols.iter().all(|symbol| symbol.is_ascii()));
+        let symbols = Symbols::preset(SymbolPreset::Ascii);
+        for border_type in [BorderType::Rounded, BorderType::Square, BorderType::Top] {
+            let (a, b, c, d, e, f) = symbols.border(&border_type);
+            assert!([a, b, c, d, e, f].iter().all(char::is_ascii));
         }
     }
 }
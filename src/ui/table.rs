@@ -1,3 +1,4 @@
+use super::text;
 use crate::Mapping;
 
 pub struct ColumnLayout {
@@ -8,11 +9,15 @@ pub struct ColumnLayout {
     pub execute: usize,
     pub description: usize,
     pub source: usize,
+    /// Width of the column listing missing programs, if it should be shown.
+    pub missing: Option<usize>,
+    /// Width of the icon column in front of the sequence, if it should be shown.
+    pub icon: Option<usize>,
 }
 
 pub fn render_header(layout: &ColumnLayout) -> String {
-    format!(
-        "{:<seq$} {:<cmd$} {:<typ$} {:<eval$} {:<exec$} {:<desc$} {:<src$}\n",
+    let header = format!(
+        "{:<seq$} {:<cmd$} {:<typ$} {:<eval$} {:<exec$} {:<desc$} {:<src$}",
         "Sequence",
         "Command",
         "Type",
@@ -27,12 +32,13 @@ pub fn render_header(layout: &ColumnLayout) -> String {
         exec = layout.execute,
         desc = layout.description,
         src = layout.source,
-    )
+    );
+    with_missing_column(layout, with_icon_column(layout, header, "Icon"), "Missing")
 }
 
 pub fn render_separator(layout: &ColumnLayout) -> String {
-    format!(
-        "{:-<seq$} {:-<cmd$} {:-<typ$} {:-<eval$} {:-<exec$} {:-<desc$} {:-<src$}\n",
+    let separator = format!(
+        "{:-<seq$} {:-<cmd$} {:-<typ$} {:-<eval$} {:-<exec$} {:-<desc$} {:-<src$}",
         "",
         "",
         "",
@@ -47,21 +53,46 @@ pub fn render_separator(layout: &ColumnLayout) -> String {
         exec = layout.execute,
         desc = layout.description,
         src = layout.source,
-    )
+    );
+    let icon_dashes = "-".repeat(layout.icon.unwrap_or_default());
+    let separator = with_icon_column(layout, separator, &icon_dashes);
+    let dashes = "-".repeat(layout.missing.unwrap_or_default());
+    with_missing_column(layout, separator, &dashes)
 }
 
-fn truncate_string(cmd: &str, max_len: usize) -> String {
-    if cmd.chars().count() > max_len {
-        cmd.chars()
-            .take(max_len.saturating_sub(3))
-            .collect::<String>()
-            + "..."
-    } else {
-        cmd.to_string()
+/// Prepends the icon column if the layout includes it.
+fn with_icon_column(layout: &ColumnLayout, line: String, cell: &str) -> String {
+    match layout.icon {
+        Some(width) => format!("{} {line}", fit(cell, width)),
+        None => line,
     }
 }
 
-pub fn render_row(layout: &ColumnLayout, sequence: &str, mapping: &Mapping) -> String {
+/// Appends the column listing missing programs if the layout includes it.
+fn with_missing_column(layout: &ColumnLayout, line: String, cell: &str) -> String {
+    match layout.missing {
+        Some(width) => format!("{line} {}\n", fit(cell, width)),
+        None => format!("{line}\n"),
+    }
+}
+
+/// Pads `cell` with spaces to `width` terminal cells, leaving longer text as is.
+fn pad(cell: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text::width(cell));
+    format!("{cell}{}", " ".repeat(padding))
+}
+
+/// Truncates `cell` to `width` terminal cells and pads it to exactly that width.
+fn fit(cell: &str, width: usize) -> String {
+    pad(&text::truncate_with_ellipsis(cell, width, "..."), width)
+}
+
+pub fn render_row(
+    layout: &ColumnLayout,
+    sequence: &str,
+    mapping: &Mapping,
+    missing: &[String],
+) -> String {
     let source = mapping
         .source_file
         .as_ref()
@@ -78,24 +109,50 @@ pub fn render_row(layout: &ColumnLayout, sequence: &str, mapping: &Mapping) -> S
         .unwrap_or_default()
         .to_string();
 
-    format!(
-        "{:<seq$} {:<cmd$} {:<typ$} {:<eval$} {:<exec$} {:<desc$} {:<src$}\n",
-        sequence,
-        truncate_string(&mapping.command, layout.command),
-        format!("{:?}", mapping.insert_type),
-        if mapping.evaluate { "Yes" } else { "No" },
-        if mapping.execute { "Yes" } else { "No" },
-        truncate_string(
-            &mapping.description.clone().unwrap_or_default(),
-            layout.description
+    let row = [
+        pad(sequence, layout.sequence),
+        fit(&mapping.command, layout.command),
+        pad(&format!("{:?}", mapping.insert_type), layout.insert_type),
+        pad(if mapping.evaluate { "Yes" } else { "No" }, layout.evaluate),
+        pad(if mapping.execute { "Yes" } else { "No" }, layout.execute),
+        fit(
+            mapping.description.as_deref().unwrap_or_default(),
+            layout.description,
         ),
-        truncate_string(&source, layout.source),
-        seq = layout.sequence,
-        cmd = layout.command,
-        typ = layout.insert_type,
-        eval = layout.evaluate,
-        exec = layout.execute,
-        desc = layout.description,
-        src = layout.source,
-    )
+        fit(&source, layout.source),
+    ]
+    .join(" ");
+    let row = with_icon_column(layout, row, mapping.icon.as_deref().unwrap_or_default());
+    with_missing_column(layout, row, &missing.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_line_up_with_wide_characters() {
+        let layout = ColumnLayout {
+            sequence: 8,
+            command: 10,
+            insert_type: 10,
+            evaluate: 9,
+            execute: 9,
+            description: 12,
+            source: 10,
+            missing: Some(6),
+            icon: Some(4),
+        };
+        let mapping = Mapping {
+            command: "echo 日本語テキスト".into(),
+            description: Some("説明".into()),
+            icon: Some("🚀".into()),
+            ..Default::default()
+        };
+        let header = render_header(&layout);
+        let row = render_row(&layout, "gé", &mapping, &["程序".into()]);
+
+        assert_eq!(text::width(&row), text::width(&header), "{row}");
+        assert!(row.contains("echo 日..."), "{row}");
+    }
 }
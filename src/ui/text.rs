@@ -0,0 +1,140 @@
+//! Measuring and cutting text by the number of terminal cells it takes up,
+//! which differs from the number of characters for wide and combining characters.
+//! Text is only ever cut between grapheme clusters, so combining characters and emoji
+//! joined by zero width joiners or followed by variation selectors stay in one piece.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Returns the number of terminal cells `text` takes up.
+pub fn width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Returns the longest start of `text` that fits into `max_width` cells.
+pub fn truncate(text: &str, max_width: usize) -> &str {
+    if width(text) <= max_width {
+        return text;
+    }
+    let end = text
+        .grapheme_indices(true)
+        .map(|(start, grapheme)| start + grapheme.len())
+        .take_while(|end| width(&text[..*end]) <= max_width)
+        .last()
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// Like [`truncate`], but ends in `ellipsis` if `text` had to be cut.
+pub fn truncate_with_ellipsis(text: &str, max_width: usize, ellipsis: &str) -> String {
+    if width(text) <= max_width {
+        return text.to_string();
+    }
+    match max_width.checked_sub(width(ellipsis)) {
+        Some(room) => format!("{}{ellipsis}", truncate(text, room)),
+        None => truncate(ellipsis, max_width).to_string(),
+    }
+}
+
+/// Like [`truncate_with_ellipsis`], but cuts off the start of `text` instead of the end.
+pub fn truncate_start_with_ellipsis(text: &str, max_width: usize, ellipsis: &str) -> String {
+    if width(text) <= max_width {
+        return text.to_string();
+    }
+    let Some(room) = max_width.checked_sub(width(ellipsis)) else {
+        return truncate(ellipsis, max_width).to_string();
+    };
+    let start = text
+        .grapheme_indices(true)
+        .map(|(start, _)| start)
+        .find(|start| width(&text[*start..]) <= room)
+        .unwrap_or(text.len());
+    format!("{ellipsis}{}", &text[start..])
+}
+
+/// Splits `text` into the start that fits into `max_width` cells and the rest.
+/// At least one grapheme goes into the start, so repeated splitting always makes progress.
+pub fn split_at_width(text: &str, max_width: usize) -> (&str, &str) {
+    let mut start = truncate(text, max_width);
+    if start.is_empty()
+        && let Some(grapheme) = text.graphemes(true).next()
+    {
+        start = grapheme;
+    }
+    text.split_at(start.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CJK: &str = "日本語";
+    const EMOJI: &str = "🚀 go";
+    const COMBINING: &str = "e\u{301}te\u{301}";
+    /// A family joined by zero width joiners and a heart turned into an emoji.
+    const JOINED: &str = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{2764}\u{fe0f}";
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width(CJK), 6);
+        assert_eq!(width(EMOJI), 5);
+        assert_eq!(width(COMBINING), 3);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate(CJK, 6), CJK);
+        // A wide character is never split in half.
+        assert_eq!(truncate(CJK, 5), "日本");
+        assert_eq!(truncate(EMOJI, 1), "");
+        assert_eq!(truncate(EMOJI, 2), "🚀");
+        // Combining characters stay with their base character.
+        assert_eq!(truncate(COMBINING, 1), "e\u{301}");
+        assert_eq!(truncate(COMBINING, 2), "e\u{301}t");
+    }
+
+    #[test]
+    fn test_truncate_with_ellipsis() {
+        assert_eq!(truncate_with_ellipsis(CJK, 6, "…"), CJK);
+        assert_eq!(truncate_with_ellipsis(CJK, 5, "…"), "日本…");
+        assert_eq!(truncate_with_ellipsis(CJK, 4, "…"), "日…");
+        assert_eq!(truncate_with_ellipsis(CJK, 1, "…"), "…");
+        assert_eq!(truncate_with_ellipsis(CJK, 0, "…"), "");
+        assert_eq!(truncate_with_ellipsis(COMBINING, 2, "…"), "e\u{301}…");
+        assert_eq!(truncate_with_ellipsis(CJK, 5, "..."), "日...");
+        assert_eq!(truncate_with_ellipsis(CJK, 2, "..."), "..");
+
+        assert_eq!(truncate_start_with_ellipsis("/a/日本語", 6, "…"), "…本語");
+        assert_eq!(truncate_start_with_ellipsis("/a/日本語", 7, "…"), "…日本語");
+        assert_eq!(truncate_start_with_ellipsis(COMBINING, 2, "…"), "…e\u{301}");
+        assert_eq!(truncate_start_with_ellipsis(COMBINING, 3, "…"), COMBINING);
+        assert_eq!(
+            truncate_start_with_ellipsis(&("x".to_owned() + COMBINING), 3, "…"),
+            "…te\u{301}"
+        );
+    }
+
+    #[test]
+    fn test_split_at_width() {
+        assert_eq!(split_at_width(CJK, 4), ("日本", "語"));
+        assert_eq!(split_at_width(CJK, 1), ("日", "本語"));
+        assert_eq!(split_at_width("", 1), ("", ""));
+    }
+
+    #[test]
+    fn test_joined_emoji_stay_whole() {
+        let (family, heart) = JOINED.split_at(JOINED.find('\u{2764}').unwrap());
+        assert_eq!(width(family), 2);
+        assert_eq!(width(heart), 2);
+
+        assert_eq!(truncate(JOINED, 3), family);
+        assert_eq!(truncate(JOINED, 1), "");
+        assert_eq!(truncate_with_ellipsis(JOINED, 3, "…"), format!("{family}…"));
+        assert_eq!(
+            truncate_start_with_ellipsis(JOINED, 3, "…"),
+            format!("…{heart}")
+        );
+        assert_eq!(split_at_width(JOINED, 1), (family, heart));
+    }
+}
@@ -1,16 +1,137 @@
-use std::{fs, path::Path};
+use std::{borrow::Cow, collections::BTreeSet, fmt::Display, path::PathBuf};
 
-use color_eyre::eyre::{Result, ensure};
+use color_eyre::eyre::{Result, WrapErr, bail, ensure};
+use crossterm::style::{Attribute, ContentStyle, StyledContent};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize)]
+use crate::{
+    layers::{Layer, Merged, Origin},
+    toml_file::{self, UnknownKeys},
+    ui::color::{Color, ColorSupport},
+};
+
+/// Colors used to render the panel.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Theme {
-    pub accent: RgbColor,
-    pub background: RgbColor,
-    pub text_highlight_primary: RgbColor,
-    pub text_highlight_secondary: RgbColor,
-    pub text_primary: RgbColor,
-    pub text_secondary: RgbColor,
+    /// Color of the panel border.
+    pub accent: Color,
+    /// Background color of the panel.
+    pub background: Color,
+    /// Color of the flags of a mapping.
+    pub text_highlight_primary: Color,
+    /// Color of the description of a mapping.
+    pub text_highlight_secondary: Color,
+    /// Color of keys, prefix groups and the typed sequence.
+    pub text_primary: Color,
+    /// Color of arrows and other secondary text.
+    pub text_secondary: Color,
+    /// Whether to ignore the colors and render with text attributes only.
+    #[serde(skip)]
+    pub monochrome: bool,
+}
+
+/// A color of the theme, referred to by its field name.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Slot {
+    Accent,
+    Background,
+    TextHighlightPrimary,
+    TextHighlightSecondary,
+    TextPrimary,
+    TextSecondary,
+}
+
+/// A color that is either one of the theme's colors, like `"text_highlight_primary"`,
+/// or a literal color in any of the formats theme files accept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThemeColor {
+    Slot(Slot),
+    Literal(Color),
+}
+
+impl ThemeColor {
+    /// Returns the closest color the terminal is able to display.
+    pub fn approximate(self, support: ColorSupport) -> Self {
+        match self {
+            ThemeColor::Slot(slot) => ThemeColor::Slot(slot),
+            ThemeColor::Literal(color) => ThemeColor::Literal(color.approximate(support)),
+        }
+    }
+}
+
+impl Serialize for ThemeColor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ThemeColor::Slot(slot) => slot.serialize(serializer),
+            ThemeColor::Literal(color) => color.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ThemeColor {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, value::StrDeserializer};
+
+        let value = toml::Value::deserialize(deserializer)?;
+        if let toml::Value::String(name) = &value {
+            if let Ok(slot) = Slot::deserialize(StrDeserializer::<D::Error>::new(name)) {
+                return Ok(ThemeColor::Slot(slot));
+            }
+            if !name.starts_with('#') {
+                return Color::deserialize(value.clone())
+                    .map(ThemeColor::Literal)
+                    .map_err(|_| {
+                        D::Error::custom(format!(
+                            "invalid color '{name}', expected a theme color like \"text_primary\", \
+                             a hex color like \"#1e1e2e\" or an ANSI color name like \"blue\""
+                        ))
+                    });
+            }
+        }
+        Color::deserialize(value)
+            .map(ThemeColor::Literal)
+            .map_err(|e| D::Error::custom(e.message()))
+    }
+}
+
+impl JsonSchema for ThemeColor {
+    fn schema_name() -> Cow<'static, str> {
+        "ThemeColor".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "The name of a color of the theme or a literal color.",
+            "anyOf": [
+                generator.subschema_for::<Slot>(),
+                generator.subschema_for::<Color>()
+            ]
+        })
+    }
+}
+
+/// The role of a piece of text in the panel, which determines how it is styled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Border,
+    /// Empty space, which only carries the background.
+    Blank,
+    Key,
+    Arrow,
+    Description,
+    /// The label of a prefix group.
+    Prefix,
+    Flags,
+    /// The typed sequence and form input.
+    Sequence,
+    /// Help text in the footer.
+    Text,
+    /// Labels and hints.
+    Muted,
+    Success,
+    Warning,
 }
 
 impl Theme {
@@ -22,6 +143,7 @@ impl Theme {
             text_highlight_secondary: rgb(220, 138, 120),
             text_primary: rgb(30, 102, 245),
             text_secondary: rgb(156, 160, 176),
+            monochrome: false,
         }
     }
 
@@ -33,6 +155,7 @@ impl Theme {
             text_highlight_secondary: rgb(242, 213, 207),
             text_primary: rgb(140, 170, 238),
             text_secondary: rgb(115, 121, 148),
+            monochrome: false,
         }
     }
 
@@ -44,6 +167,7 @@ impl Theme {
             text_highlight_secondary: rgb(244, 219, 214),
             text_primary: rgb(138, 173, 244),
             text_secondary: rgb(110, 115, 141),
+            monochrome: false,
         }
     }
 
@@ -55,28 +179,341 @@ impl Theme {
             text_highlight_secondary: rgb(245, 224, 220), // Rosewater
             text_primary: rgb(137, 180, 250),             // Blue
             text_secondary: rgb(108, 112, 134),           // Overlay0
+            monochrome: false,
+        }
+    }
+
+    pub fn dracula() -> Self {
+        Self {
+            accent: rgb(189, 147, 249),
+            background: rgb(40, 42, 54),
+            text_highlight_primary: rgb(255, 184, 108),
+            text_highlight_secondary: rgb(248, 248, 242),
+            text_primary: rgb(139, 233, 253),
+            text_secondary: rgb(98, 114, 164),
+            monochrome: false,
+        }
+    }
+
+    pub fn gruvbox_dark() -> Self {
+        Self {
+            accent: rgb(131, 165, 152),
+            background: rgb(29, 32, 33),
+            text_highlight_primary: rgb(254, 128, 25),
+            text_highlight_secondary: rgb(235, 219, 178),
+            text_primary: rgb(131, 165, 152),
+            text_secondary: rgb(146, 131, 116),
+            monochrome: false,
+        }
+    }
+
+    pub fn gruvbox_light() -> Self {
+        Self {
+            accent: rgb(7, 102, 120),
+            background: rgb(249, 245, 215),
+            text_highlight_primary: rgb(175, 58, 3),
+            text_highlight_secondary: rgb(60, 56, 54),
+            text_primary: rgb(7, 102, 120),
+            text_secondary: rgb(146, 131, 116),
+            monochrome: false,
+        }
+    }
+
+    pub fn nord() -> Self {
+        Self {
+            accent: rgb(136, 192, 208),
+            background: rgb(46, 52, 64),
+            text_highlight_primary: rgb(208, 135, 112),
+            text_highlight_secondary: rgb(236, 239, 244),
+            text_primary: rgb(129, 161, 193),
+            text_secondary: rgb(76, 86, 106),
+            monochrome: false,
+        }
+    }
+
+    pub fn one_dark() -> Self {
+        Self {
+            accent: rgb(97, 175, 239),
+            background: rgb(40, 44, 52),
+            text_highlight_primary: rgb(209, 154, 102),
+            text_highlight_secondary: rgb(171, 178, 191),
+            text_primary: rgb(97, 175, 239),
+            text_secondary: rgb(92, 99, 112),
+            monochrome: false,
+        }
+    }
+
+    pub fn solarized_dark() -> Self {
+        Self {
+            accent: rgb(38, 139, 210),
+            background: rgb(0, 43, 54),
+            text_highlight_primary: rgb(203, 75, 22),
+            text_highlight_secondary: rgb(147, 161, 161),
+            text_primary: rgb(38, 139, 210),
+            text_secondary: rgb(88, 110, 117),
+            monochrome: false,
         }
     }
 
-    pub fn load(config_dir: &Path, theme_name: &str) -> Result<Self> {
-        let theme = match theme_name {
-            "catppuccin-mocha" => Self::catppuccin_mocha(),
-            "catppuccin-macchiato" => Self::catppuccin_macchiato(),
-            "catppuccin-frappe" => Self::catppuccin_frappe(),
-            "catppuccin-latte" => Self::catppuccin_latte(),
-            other => {
-                let theme_path = config_dir.join("themes").join(format!("{other}.toml"));
+    pub fn solarized_light() -> Self {
+        Self {
+            accent: rgb(38, 139, 210),
+            background: rgb(253, 246, 227),
+            text_highlight_primary: rgb(203, 75, 22),
+            text_highlight_secondary: rgb(88, 110, 117),
+            text_primary: rgb(38, 139, 210),
+            text_secondary: rgb(147, 161, 161),
+            monochrome: false,
+        }
+    }
+
+    pub fn tokyo_night() -> Self {
+        Self {
+            accent: rgb(122, 162, 247),
+            background: rgb(26, 27, 38),
+            text_highlight_primary: rgb(255, 158, 100),
+            text_highlight_secondary: rgb(192, 202, 245),
+            text_primary: rgb(122, 162, 247),
+            text_secondary: rgb(86, 95, 137),
+            monochrome: false,
+        }
+    }
+
+    /// Returns the theme with every color replaced by the closest one the terminal can display.
+    pub fn approximate(self, support: ColorSupport) -> Self {
+        Self {
+            accent: self.accent.approximate(support),
+            background: self.background.approximate(support),
+            text_highlight_primary: self.text_highlight_primary.approximate(support),
+            text_highlight_secondary: self.text_highlight_secondary.approximate(support),
+            text_primary: self.text_primary.approximate(support),
+            text_secondary: self.text_secondary.approximate(support),
+            monochrome: self.monochrome,
+        }
+    }
+
+    /// Returns this theme rendering with bold, underlined and reversed text instead of colors.
+    pub fn monochrome(self) -> Self {
+        Self {
+            monochrome: true,
+            ..self
+        }
+    }
+
+    /// Styles `content` according to its role.
+    pub fn style<D: Display>(&self, content: D, role: Role) -> StyledContent<D> {
+        self.style_with(content, role, None)
+    }
+
+    /// Like [`Theme::style`], but with `color` instead of the role's color.
+    /// Monochrome themes ignore the color.
+    pub fn style_with<D: Display>(
+        &self,
+        content: D,
+        role: Role,
+        color: Option<ThemeColor>,
+    ) -> StyledContent<D> {
+        let mut style = ContentStyle::new();
+        if self.monochrome {
+            let attribute = match role {
+                Role::Key | Role::Flags | Role::Sequence => Some(Attribute::Bold),
+                Role::Prefix | Role::Success => Some(Attribute::Underlined),
+                Role::Warning => Some(Attribute::Reverse),
+                Role::Border
+                | Role::Blank
+                | Role::Arrow
+                | Role::Description
+                | Role::Text
+                | Role::Muted => None,
+            };
+            if let Some(attribute) = attribute {
+                style.attributes.set(attribute);
+            }
+        } else {
+            let foreground = match role {
+                Role::Border => Some(self.accent),
+                Role::Blank => None,
+                Role::Key | Role::Prefix | Role::Sequence | Role::Text => Some(self.text_primary),
+                Role::Arrow | Role::Muted => Some(self.text_secondary),
+                Role::Description | Role::Success => Some(self.text_highlight_secondary),
+                Role::Flags | Role::Warning => Some(self.text_highlight_primary),
+            };
+            let foreground = color.map(|color| self.resolve(color)).or(foreground);
+            style.foreground_color = foreground.map(Into::into);
+            style.background_color = Some(self.background.into());
+        }
+        StyledContent::new(style, content)
+    }
+
+    /// Marks a styled piece of text as highlighted, drawing it on the accent color.
+    /// Monochrome themes reverse it instead.
+    pub fn highlight(&self, style: &mut ContentStyle) {
+        if self.monochrome {
+            style.attributes.set(Attribute::Reverse);
+        } else {
+            style.foreground_color = Some(self.background.into());
+            style.background_color = Some(self.accent.into());
+        }
+    }
+
+    /// Names of all builtin themes.
+    pub const BUILTIN_NAMES: &[&str] = &[
+        "catppuccin-frappe",
+        "catppuccin-latte",
+        "catppuccin-macchiato",
+        "catppuccin-mocha",
+        "dracula",
+        "gruvbox-dark",
+        "gruvbox-light",
+        "nord",
+        "one-dark",
+        "solarized-dark",
+        "solarized-light",
+        "tokyo-night",
+    ];
+
+    pub fn builtin(theme_name: &str) -> Option<Self> {
+        match theme_name {
+            "catppuccin-mocha" => Some(Self::catppuccin_mocha()),
+            "catppuccin-macchiato" => Some(Self::catppuccin_macchiato()),
+            "catppuccin-frappe" => Some(Self::catppuccin_frappe()),
+            "catppuccin-latte" => Some(Self::catppuccin_latte()),
+            "dracula" => Some(Self::dracula()),
+            "gruvbox-dark" => Some(Self::gruvbox_dark()),
+            "gruvbox-light" => Some(Self::gruvbox_light()),
+            "nord" => Some(Self::nord()),
+            "one-dark" => Some(Self::one_dark()),
+            "solarized-dark" => Some(Self::solarized_dark()),
+            "solarized-light" => Some(Self::solarized_light()),
+            "tokyo-night" => Some(Self::tokyo_night()),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of all themes defined in `themes/` of the given config directories
+    /// that are not builtin, sorted alphabetically.
+    pub fn user_theme_names(config_dirs: &[PathBuf]) -> Result<Vec<String>> {
+        let mut names = BTreeSet::new();
+        for config_dir in config_dirs {
+            let themes_dir = config_dir.join("themes");
+            if !themes_dir.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&themes_dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+                    continue;
+                }
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str())
+                    && Self::builtin(name).is_none()
+                {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    /// Loads a theme from `themes/<name>.toml` in the given config directories,
+    /// lowest priority first. Theme files override the theme they `extend` field by field,
+    /// or the builtin theme of the same name if they don't extend one.
+    pub fn load(
+        config_dirs: &[PathBuf],
+        theme_name: &str,
+        unknown_keys: UnknownKeys,
+    ) -> Result<Self> {
+        Self::load_extending(config_dirs, theme_name, unknown_keys, &mut Vec::new())
+    }
+
+    fn load_extending(
+        config_dirs: &[PathBuf],
+        theme_name: &str,
+        unknown_keys: UnknownKeys,
+        stack: &mut Vec<String>,
+    ) -> Result<Self> {
+        if stack.iter().any(|name| name == theme_name) {
+            stack.push(theme_name.to_string());
+            bail!("Theme inheritance cycle: {}", stack.join(" → "));
+        }
+        stack.push(theme_name.to_string());
+
+        let theme_paths: Vec<_> = config_dirs
+            .iter()
+            .map(|config_dir| config_dir.join("themes").join(format!("{theme_name}.toml")))
+            .collect();
+
+        let mut layers = Vec::new();
+        for path in &theme_paths {
+            layers.extend(Layer::read::<ThemeFile>(path)?);
+        }
+
+        // Like any other key, `extends` of a higher priority file wins.
+        let extends = layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.table.get("extends")?.as_str());
+        let base = match (extends, Self::builtin(theme_name)) {
+            (Some(parent), _) => {
+                let parent = Self::load_extending(config_dirs, parent, unknown_keys, stack)?;
+                toml::Table::try_from(parent)?
+            }
+            (None, Some(theme)) => toml::Table::try_from(theme)?,
+            (None, None) => {
                 ensure!(
-                    theme_path.exists(),
-                    "Theme '{other}' not found at {:?}",
-                    theme_path
+                    !layers.is_empty(),
+                    "Theme '{theme_name}' not found at {:?}",
+                    theme_paths
                 );
-                let contents = fs::read_to_string(&theme_path)?;
-                toml::from_str(&contents)?
+                toml::Table::new()
             }
         };
+
+        let mut merged = Merged::new(base, Origin::Default);
+        for layer in &layers {
+            merged.merge(layer);
+        }
+        merged.table.remove("extends");
+        let (theme, unknown) = merged.deserialize(&layers).wrap_err_with(|| {
+            format!(
+                "Theme '{theme_name}' has to define all colors or extend another theme, \
+                 e.g. `extends = \"catppuccin-mocha\"`"
+            )
+        })?;
+        toml_file::report_unknown_keys(&unknown, unknown_keys)?;
+
+        stack.pop();
         Ok(theme)
     }
+
+    /// Returns the color a [`ThemeColor`] refers to.
+    pub fn resolve(&self, color: ThemeColor) -> Color {
+        match color {
+            ThemeColor::Slot(Slot::Accent) => self.accent,
+            ThemeColor::Slot(Slot::Background) => self.background,
+            ThemeColor::Slot(Slot::TextHighlightPrimary) => self.text_highlight_primary,
+            ThemeColor::Slot(Slot::TextHighlightSecondary) => self.text_highlight_secondary,
+            ThemeColor::Slot(Slot::TextPrimary) => self.text_primary,
+            ThemeColor::Slot(Slot::TextSecondary) => self.text_secondary,
+            ThemeColor::Literal(color) => color,
+        }
+    }
+
+    /// Returns the theme with the colors set in `overrides` replaced.
+    pub fn with_overrides(self, overrides: &ThemeFile) -> Self {
+        Self {
+            accent: overrides.accent.unwrap_or(self.accent),
+            background: overrides.background.unwrap_or(self.background),
+            text_highlight_primary: overrides
+                .text_highlight_primary
+                .unwrap_or(self.text_highlight_primary),
+            text_highlight_secondary: overrides
+                .text_highlight_secondary
+                .unwrap_or(self.text_highlight_secondary),
+            text_primary: overrides.text_primary.unwrap_or(self.text_primary),
+            text_secondary: overrides.text_secondary.unwrap_or(self.text_secondary),
+            monochrome: self.monochrome,
+        }
+    }
 }
 
 impl std::default::Default for Theme {
@@ -85,23 +522,84 @@ impl std::default::Default for Theme {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct RgbColor {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
+/// A theme file in the `themes/` directory or the inline `[panel.theme]` table,
+/// which only needs to set the colors it changes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ThemeFile {
+    /// Name of a builtin or user theme to take all colors not set here from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Color of the panel border.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent: Option<Color>,
+    /// Background color of the panel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Color>,
+    /// Color of the flags of a mapping.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_highlight_primary: Option<Color>,
+    /// Color of the description of a mapping.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_highlight_secondary: Option<Color>,
+    /// Color of keys, prefix groups and the typed sequence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_primary: Option<Color>,
+    /// Color of arrows and other secondary text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_secondary: Option<Color>,
 }
 
-impl From<RgbColor> for crossterm::style::Color {
-    fn from(rgb: RgbColor) -> Self {
-        crossterm::style::Color::Rgb {
-            r: rgb.r,
-            g: rgb.g,
-            b: rgb.b,
-        }
+impl ThemeFile {
+    pub fn is_empty(&self) -> bool {
+        self.extends.is_none()
+            && self.accent.is_none()
+            && self.background.is_none()
+            && self.text_highlight_primary.is_none()
+            && self.text_highlight_secondary.is_none()
+            && self.text_primary.is_none()
+            && self.text_secondary.is_none()
     }
 }
 
-fn rgb(r: u8, g: u8, b: u8) -> RgbColor {
-    RgbColor { r, g, b }
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_extends() {
+        let dir = TempDir::with_files(
+            "extends",
+            &[
+                ("themes/base.toml", "extends = \"nord\"\naccent = \"red\"\n"),
+                (
+                    "themes/child.toml",
+                    "extends = \"base\"\nbackground = 236\n",
+                ),
+                ("themes/loop-a.toml", "extends = \"loop-b\"\n"),
+                ("themes/loop-b.toml", "extends = \"loop-a\"\n"),
+            ],
+        );
+
+        let config_dirs = &[dir.path().to_path_buf()];
+        let theme = Theme::load(config_dirs, "child", UnknownKeys::Error).unwrap();
+        assert_eq!(
+            theme.accent,
+            Color::Named(crate::ui::color::NamedColor::Red)
+        );
+        assert_eq!(theme.background, Color::Indexed(236));
+        assert_eq!(theme.text_primary, Theme::nord().text_primary);
+
+        let Err(err) = Theme::load(config_dirs, "loop-a", UnknownKeys::Error) else {
+            panic!("cycle was not detected");
+        };
+        assert_eq!(
+            err.to_string(),
+            "Theme inheritance cycle: loop-a → loop-b → loop-a"
+        );
+    }
 }